@@ -0,0 +1,286 @@
+use std::str::FromStr;
+
+use serde_json::Value;
+
+/// The ways the two location lists can be written down.
+///
+/// - `Whitespace`: the puzzle input, one pair per line, e.g. `3   4`
+/// - `Csv` and `Tsv`: one pair per row, optionally with a header row
+/// - `Json`: an object of two arrays, e.g. `{"left": [3, 4], "right": [4, 3]}`
+///
+/// `Auto` picks one of the above by looking at the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    Auto,
+    Whitespace,
+    Csv,
+    Tsv,
+    Json,
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(InputFormat::Auto),
+            "whitespace" | "txt" => Ok(InputFormat::Whitespace),
+            "csv" => Ok(InputFormat::Csv),
+            "tsv" => Ok(InputFormat::Tsv),
+            "json" => Ok(InputFormat::Json),
+            _ => Err(format!("Unknown input format: {s}")),
+        }
+    }
+}
+
+impl InputFormat {
+    /// Guesses the format from the first line of the input
+    pub fn detect(input: &str) -> Self {
+        if input.trim_start().starts_with('{') {
+            return InputFormat::Json;
+        }
+
+        let first_line = input.lines().find(|line| !line.trim().is_empty());
+        match first_line {
+            Some(line) if line.contains('\t') => InputFormat::Tsv,
+            Some(line) if line.contains(',') => InputFormat::Csv,
+            _ => InputFormat::Whitespace,
+        }
+    }
+}
+
+/// Names of the columns (or JSON keys) holding the left and the right list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Columns {
+    pub left: String,
+    pub right: String,
+}
+
+impl FromStr for Columns {
+    type Err = String;
+
+    /// Parses `left,right`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(',') {
+            Some((left, right)) => Ok(Columns {
+                left: left.trim().to_string(),
+                right: right.trim().to_string(),
+            }),
+            None => Err(format!(
+                "Expected two column names like `left,right`, got: {s}"
+            )),
+        }
+    }
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        Columns {
+            left: "left".to_string(),
+            right: "right".to_string(),
+        }
+    }
+}
+
+/// Reads the left and the right list of location IDs from the input.
+///
+/// For the row-based formats the first row is a header if it is not made of
+/// numbers. Without `columns`, the `left` and `right` columns of the header are
+/// used when it has them, the first two columns otherwise.
+/// For JSON, `columns` are the keys of the two arrays, `left` and `right` by default.
+pub fn parse_lists(
+    input: &str,
+    format: InputFormat,
+    columns: Option<&Columns>,
+) -> Result<(Vec<u32>, Vec<u32>), String> {
+    let (lnums, rnums) = match format {
+        InputFormat::Auto => return parse_lists(input, InputFormat::detect(input), columns),
+        InputFormat::Whitespace => parse_rows(input, None, columns)?,
+        InputFormat::Csv => parse_rows(input, Some(','), columns)?,
+        InputFormat::Tsv => parse_rows(input, Some('\t'), columns)?,
+        InputFormat::Json => parse_json(input, columns)?,
+    };
+
+    if lnums.len() != rnums.len() {
+        return Err(format!(
+            "The lists have different lengths: {} and {}",
+            lnums.len(),
+            rnums.len()
+        ));
+    }
+    Ok((lnums, rnums))
+}
+
+fn split_fields(line: &str, delimiter: Option<char>) -> Vec<&str> {
+    let fields: Vec<&str> = match delimiter {
+        Some(d) => line.split(d).collect(),
+        None => line.split_whitespace().collect(),
+    };
+
+    fields
+        .iter()
+        .map(|field| field.trim().trim_matches('"'))
+        .collect()
+}
+
+fn parse_rows(
+    input: &str,
+    delimiter: Option<char>,
+    columns: Option<&Columns>,
+) -> Result<(Vec<u32>, Vec<u32>), String> {
+    // Keep the line numbers for the error messages
+    let mut rows = input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| (i + 1, split_fields(line, delimiter)))
+        .peekable();
+
+    // The first row is a header when none of its fields is a number, or when it names
+    // the requested columns, otherwise a bad field in it is as bad as in any other
+    let is_header = |fields: &[&str]| {
+        fields.iter().all(|f| f.parse::<i64>().is_err())
+            || columns.is_some_and(|c| {
+                [&c.left, &c.right]
+                    .iter()
+                    .all(|name| fields.iter().any(|f| f.eq_ignore_ascii_case(name)))
+            })
+    };
+    let header: Option<Vec<&str>> = match rows.peek() {
+        Some((_, fields)) if is_header(fields) => rows.next().map(|(_, fields)| fields),
+        _ => None,
+    };
+
+    let find = |header: &[&str], name: &str| -> Result<usize, String> {
+        header
+            .iter()
+            .position(|h| h.eq_ignore_ascii_case(name))
+            .ok_or(format!("No column named `{name}` in the header"))
+    };
+    let (lidx, ridx) = match (&header, columns) {
+        (Some(header), Some(columns)) => {
+            (find(header, &columns.left)?, find(header, &columns.right)?)
+        }
+        (None, Some(_)) => {
+            return Err("Columns can only be selected by name with a header".to_string())
+        }
+        (Some(header), None) => {
+            let default = Columns::default();
+            match (find(header, &default.left), find(header, &default.right)) {
+                (Ok(l), Ok(r)) => (l, r),
+                _ => (0, 1),
+            }
+        }
+        (None, None) => (0, 1),
+    };
+
+    let mut lnums: Vec<u32> = Vec::new();
+    let mut rnums: Vec<u32> = Vec::new();
+    for (line, fields) in rows {
+        let get = |idx: usize| -> Result<u32, String> {
+            let field = fields
+                .get(idx)
+                .ok_or(format!("line {line}: missing column {}", idx + 1))?;
+            field
+                .parse()
+                .map_err(|_| format!("line {line}: `{field}` is not a location ID"))
+        };
+        lnums.push(get(lidx)?);
+        rnums.push(get(ridx)?);
+    }
+
+    Ok((lnums, rnums))
+}
+
+fn parse_json(input: &str, columns: Option<&Columns>) -> Result<(Vec<u32>, Vec<u32>), String> {
+    let value: Value = serde_json::from_str(input).map_err(|e| format!("Invalid JSON: {e}"))?;
+    let default = Columns::default();
+    let columns = columns.unwrap_or(&default);
+
+    let get = |key: &str| -> Result<Vec<u32>, String> {
+        value
+            .get(key)
+            .and_then(Value::as_array)
+            .ok_or(format!("No array named `{key}` in the JSON"))?
+            .iter()
+            .map(|v| {
+                v.as_u64()
+                    .and_then(|n| u32::try_from(n).ok())
+                    .ok_or(format!("`{v}` in `{key}` is not a location ID"))
+            })
+            .collect()
+    };
+
+    Ok((get(&columns.left)?, get(&columns.right)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPECTED: ([u32; 3], [u32; 3]) = ([3, 4, 2], [4, 3, 5]);
+
+    fn parse(input: &str, columns: Option<&str>) -> Result<(Vec<u32>, Vec<u32>), String> {
+        let columns: Option<Columns> = columns.map(|c| c.parse().unwrap());
+        parse_lists(input, InputFormat::Auto, columns.as_ref())
+    }
+
+    #[test]
+    fn detects_the_formats() {
+        assert_eq!(InputFormat::Whitespace, InputFormat::detect("3   4\n"));
+        assert_eq!(InputFormat::Csv, InputFormat::detect("\nleft,right\n3,4\n"));
+        assert_eq!(InputFormat::Tsv, InputFormat::detect("3\t4\n"));
+        assert_eq!(InputFormat::Json, InputFormat::detect(" {\"left\": []}"));
+    }
+
+    #[test]
+    fn parses_every_format() {
+        let inputs = [
+            "3   4\n4   3\n2   5\n",
+            "3,4\n4,3\n2,5\n",
+            "left,right\n3,4\n4,3\n2,5\n",
+            "\"right\", \"left\"\n4, 3\n3, 4\n5, 2\n",
+            "right\tleft\n4\t3\n3\t4\n5\t2\n",
+            "{\"left\": [3, 4, 2], \"right\": [4, 3, 5]}",
+        ];
+        for input in inputs {
+            let (l, r) = parse(input, None).unwrap();
+            assert_eq!(
+                (EXPECTED.0.to_vec(), EXPECTED.1.to_vec()),
+                (l, r),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn selects_columns_by_name() {
+        let csv = "id,team_a,note,team_b\n1,3,x,4\n2,4,y,3\n3,2,z,5\n";
+        let (l, r) = parse(csv, Some("team_a,team_b")).unwrap();
+        assert_eq!((EXPECTED.0.to_vec(), EXPECTED.1.to_vec()), (l, r));
+
+        let json = "{\"a\": [3, 4, 2], \"b\": [4, 3, 5]}";
+        let (l, r) = parse(json, Some("a, b")).unwrap();
+        assert_eq!((EXPECTED.0.to_vec(), EXPECTED.1.to_vec()), (l, r));
+    }
+
+    #[test]
+    fn reports_bad_input() {
+        assert!(parse("left,right\n3,4\n4,x\n", None)
+            .unwrap_err()
+            .contains("line 3"));
+        assert!(parse("3,4\n", Some("left,right")).is_err());
+        // A bad first row is not taken for a header
+        for (input, field) in [("3,-4\n4,3\n", "-4"), ("3,x\n4,3\n", "x"), ("3   x\n", "x")] {
+            assert_eq!(
+                Err(format!("line 1: `{field}` is not a location ID")),
+                parse(input, None)
+            );
+        }
+        let (l, r) = parse("n,left,right,2\n0,3,4,0\n", Some("left,right")).unwrap();
+        assert_eq!((vec![3], vec![4]), (l, r));
+        assert!(parse("a,b\n3,4\n", Some("left,b")).is_err());
+        assert!(parse("{\"left\": [1, 2], \"right\": [1]}", None).is_err());
+        assert!(parse("{\"left\": [-1], \"right\": [1]}", None).is_err());
+    }
+}
@@ -0,0 +1,390 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Keeps the similarity score of the two location lists up to date
+/// while IDs are inserted into or removed from either side.
+///
+/// The score is the sum of `id * left_count(id) * right_count(id)`, so
+/// changing the count of one ID on one side only changes that ID's term.
+/// Every update is a couple of hash map lookups, O(1) on average.
+#[derive(Debug, Default)]
+pub struct SimilarityIndex {
+    lmap: HashMap<u32, u32>,
+    rmap: HashMap<u32, u32>,
+    score: u64,
+}
+
+impl SimilarityIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn similarity(&self) -> u64 {
+        self.score
+    }
+
+    pub fn insert_left(&mut self, id: u32) {
+        *self.lmap.entry(id).or_insert(0) += 1;
+        self.score += id as u64 * *self.rmap.get(&id).unwrap_or(&0) as u64;
+    }
+
+    pub fn insert_right(&mut self, id: u32) {
+        *self.rmap.entry(id).or_insert(0) += 1;
+        self.score += id as u64 * *self.lmap.get(&id).unwrap_or(&0) as u64;
+    }
+
+    /// Returns `false` if the ID is not in the left list.
+    pub fn remove_left(&mut self, id: u32) -> bool {
+        if !decrement(&mut self.lmap, id) {
+            return false;
+        }
+        self.score -= id as u64 * *self.rmap.get(&id).unwrap_or(&0) as u64;
+        true
+    }
+
+    /// Returns `false` if the ID is not in the right list.
+    pub fn remove_right(&mut self, id: u32) -> bool {
+        if !decrement(&mut self.rmap, id) {
+            return false;
+        }
+        self.score -= id as u64 * *self.lmap.get(&id).unwrap_or(&0) as u64;
+        true
+    }
+}
+
+fn decrement(map: &mut HashMap<u32, u32>, id: u32) -> bool {
+    match map.get_mut(&id) {
+        Some(1) => {
+            map.remove(&id);
+            true
+        }
+        Some(count) => {
+            *count -= 1;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Keeps the total distance of the two location lists up to date
+/// while pairs of IDs are inserted or removed.
+///
+/// The distance is only defined for lists of the same length, so the
+/// updates come in pairs, one ID for each list, like the lines of the input.
+///
+/// Inserting `l` at rank `a` of the left list and `r` at rank `b` of the right
+/// list only changes the pairing of ranks between `a` and `b`: everything below
+/// is untouched, and everything above is shifted by one on both sides.
+/// Both lists are order-statistics trees, so an update costs
+/// O((|a - b| + 1) * log(n)) instead of sorting everything again.
+#[derive(Debug, Default)]
+pub struct DistanceIndex {
+    left: OrderStatisticTree,
+    right: OrderStatisticTree,
+    distance: u64,
+}
+
+impl DistanceIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn distance(&self) -> u64 {
+        self.distance
+    }
+
+    pub fn insert_pair(&mut self, l: u32, r: u32) {
+        let a = self.left.rank(l);
+        let b = self.right.rank(r);
+        let (lo, hi) = (a.min(b), a.max(b));
+
+        // The old ranks `lo..hi` are replaced by the new ranks `lo..=hi`
+        self.distance -= self.pair_distance(lo, hi);
+        self.left.insert(l);
+        self.right.insert(r);
+        self.distance += self.pair_distance(lo, hi + 1);
+    }
+
+    /// Returns `false` and leaves the lists untouched if either
+    /// of the IDs is not in its list.
+    pub fn remove_pair(&mut self, l: u32, r: u32) -> bool {
+        if !self.left.contains(l) || !self.right.contains(r) {
+            return false;
+        }
+        let a = self.left.rank(l);
+        let b = self.right.rank(r);
+        let (lo, hi) = (a.min(b), a.max(b));
+
+        // The reverse of the insertion: the old ranks `lo..=hi` become `lo..hi`
+        self.distance -= self.pair_distance(lo, hi + 1);
+        self.left.remove(l);
+        self.right.remove(r);
+        self.distance += self.pair_distance(lo, hi);
+        true
+    }
+
+    /// Sum of the distances of the pairs with rank in `from..to`
+    fn pair_distance(&self, from: usize, to: usize) -> u64 {
+        (from..to)
+            .map(|i| {
+                let l = self.left.select(i).unwrap();
+                let r = self.right.select(i).unwrap();
+                l.abs_diff(r) as u64
+            })
+            .sum()
+    }
+}
+
+#[derive(Debug)]
+struct Node {
+    key: u32,
+    priority: u32,
+    size: usize,
+    left: Option<Box<Node>>,
+    right: Option<Box<Node>>,
+}
+
+impl Node {
+    fn new(key: u32, priority: u32) -> Box<Self> {
+        Box::new(Node {
+            key,
+            priority,
+            size: 1,
+            left: None,
+            right: None,
+        })
+    }
+
+    fn update(&mut self) {
+        self.size = 1 + size(&self.left) + size(&self.right);
+    }
+}
+
+fn size(node: &Option<Box<Node>>) -> usize {
+    node.as_ref().map_or(0, |n| n.size)
+}
+
+/// Multiset of IDs backed by a treap where every node knows the size of
+/// its subtree, so it can answer "how many IDs are smaller than `x`" and
+/// "which ID has rank `k`" in O(log(n)) on top of insertion and removal.
+#[derive(Debug)]
+pub struct OrderStatisticTree {
+    root: Option<Box<Node>>,
+    seed: u32,
+}
+
+impl Default for OrderStatisticTree {
+    fn default() -> Self {
+        OrderStatisticTree {
+            root: None,
+            seed: 0x9E37_79B9,
+        }
+    }
+}
+
+impl OrderStatisticTree {
+    pub fn contains(&self, key: u32) -> bool {
+        let mut node = &self.root;
+        while let Some(n) = node {
+            node = match key.cmp(&n.key) {
+                Ordering::Less => &n.left,
+                Ordering::Greater => &n.right,
+                Ordering::Equal => return true,
+            };
+        }
+        false
+    }
+
+    /// Number of keys strictly smaller than `key`
+    pub fn rank(&self, key: u32) -> usize {
+        let mut rank = 0;
+        let mut node = &self.root;
+        while let Some(n) = node {
+            if key <= n.key {
+                node = &n.left;
+            } else {
+                rank += size(&n.left) + 1;
+                node = &n.right;
+            }
+        }
+        rank
+    }
+
+    /// The key with `k` keys before it, counting from zero
+    pub fn select(&self, mut k: usize) -> Option<u32> {
+        let mut node = &self.root;
+        while let Some(n) = node {
+            let lsize = size(&n.left);
+            match k.cmp(&lsize) {
+                Ordering::Less => node = &n.left,
+                Ordering::Equal => return Some(n.key),
+                Ordering::Greater => {
+                    k -= lsize + 1;
+                    node = &n.right;
+                }
+            }
+        }
+        None
+    }
+
+    pub fn insert(&mut self, key: u32) {
+        let priority = self.next_priority();
+        let (lt, ge) = split(self.root.take(), key, false);
+        self.root = merge(merge(lt, Some(Node::new(key, priority))), ge);
+    }
+
+    /// Removes one occurrence of `key`, returns `false` if there is none
+    pub fn remove(&mut self, key: u32) -> bool {
+        let (lt, ge) = split(self.root.take(), key, false);
+        let (eq, gt) = split(ge, key, true);
+        let removed = eq.is_some();
+        let eq = eq.and_then(|n| merge(n.left, n.right));
+        self.root = merge(merge(lt, eq), gt);
+        removed
+    }
+
+    // Xorshift, the priorities only need to look random
+    fn next_priority(&mut self) -> u32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        self.seed
+    }
+}
+
+/// Splits the tree into the keys before `key` and the rest.
+/// With `inclusive` the keys equal to `key` go to the first part.
+fn split(
+    node: Option<Box<Node>>,
+    key: u32,
+    inclusive: bool,
+) -> (Option<Box<Node>>, Option<Box<Node>>) {
+    match node {
+        None => (None, None),
+        Some(mut n) => {
+            let goes_left = if inclusive { n.key <= key } else { n.key < key };
+            if goes_left {
+                let (l, r) = split(n.right.take(), key, inclusive);
+                n.right = l;
+                n.update();
+                (Some(n), r)
+            } else {
+                let (l, r) = split(n.left.take(), key, inclusive);
+                n.left = r;
+                n.update();
+                (l, Some(n))
+            }
+        }
+    }
+}
+
+/// Joins two trees, every key of `a` must not be greater than the keys of `b`
+fn merge(a: Option<Box<Node>>, b: Option<Box<Node>>) -> Option<Box<Node>> {
+    match (a, b) {
+        (None, b) => b,
+        (a, None) => a,
+        (Some(mut a), Some(mut b)) => {
+            if a.priority > b.priority {
+                a.right = merge(a.right.take(), Some(b));
+                a.update();
+                Some(a)
+            } else {
+                b.left = merge(Some(a), b.left.take());
+                b.update();
+                Some(b)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEFT: [u32; 6] = [3, 4, 2, 1, 3, 3];
+    const RIGHT: [u32; 6] = [4, 3, 5, 3, 9, 3];
+
+    #[test]
+    fn similarity_index_matches_example() {
+        let mut index = SimilarityIndex::new();
+        LEFT.iter().for_each(|&l| index.insert_left(l));
+        RIGHT.iter().for_each(|&r| index.insert_right(r));
+        assert_eq!(31, index.similarity());
+
+        // One `3` less on the right: each of the three left `3` loses 3
+        assert!(index.remove_right(3));
+        assert_eq!(22, index.similarity());
+        assert!(!index.remove_left(7));
+
+        index.insert_right(1);
+        assert_eq!(23, index.similarity());
+    }
+
+    #[test]
+    fn distance_index_matches_example() {
+        let mut index = DistanceIndex::new();
+        LEFT.iter()
+            .zip(RIGHT.iter())
+            .for_each(|(&l, &r)| index.insert_pair(l, r));
+        assert_eq!(11, index.distance());
+
+        assert!(index.remove_pair(1, 9));
+        // Left 2 3 3 3 4, right 3 3 3 4 5
+        assert_eq!(3, index.distance());
+        assert!(!index.remove_pair(1, 3));
+        assert_eq!(3, index.distance());
+    }
+
+    #[test]
+    fn distance_index_matches_recomputation() {
+        let mut seed: u32 = 12345;
+        let mut next = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed % 50
+        };
+
+        let mut index = DistanceIndex::new();
+        let mut pairs: Vec<(u32, u32)> = Vec::new();
+        for step in 0..500 {
+            if step % 3 == 2 && !pairs.is_empty() {
+                let (l, r) = pairs.swap_remove(next() as usize % pairs.len());
+                assert!(index.remove_pair(l, r));
+            } else {
+                let (l, r) = (next(), next());
+                pairs.push((l, r));
+                index.insert_pair(l, r);
+            }
+
+            let mut lnums: Vec<u32> = pairs.iter().map(|p| p.0).collect();
+            let mut rnums: Vec<u32> = pairs.iter().map(|p| p.1).collect();
+            lnums.sort();
+            rnums.sort();
+            let expected: u64 = lnums
+                .iter()
+                .zip(&rnums)
+                .map(|(l, r)| l.abs_diff(*r) as u64)
+                .sum();
+            assert_eq!(expected, index.distance());
+        }
+    }
+
+    #[test]
+    fn order_statistic_tree_with_duplicates() {
+        let mut tree = OrderStatisticTree::default();
+        [5, 1, 5, 3].iter().for_each(|&k| tree.insert(k));
+        assert_eq!(4, tree.rank(u32::MAX));
+        assert_eq!(2, tree.rank(5));
+        assert_eq!(Some(5), tree.select(3));
+        assert_eq!(None, tree.select(4));
+        assert!(tree.remove(5));
+        assert!(!tree.remove(4));
+        assert_eq!(
+            vec![1, 3, 5],
+            (0..3)
+                .map(|k| tree.select(k).unwrap())
+                .collect::<Vec<u32>>()
+        );
+    }
+}
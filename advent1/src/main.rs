@@ -1,6 +1,13 @@
 use std::collections::{BinaryHeap, HashMap};
+use std::env;
 use std::fs;
 
+mod format;
+mod index;
+
+use format::{parse_lists, Columns, InputFormat};
+use index::{DistanceIndex, SimilarityIndex};
+
 /// Upon pouring into the office, everyone confirms that the Chief Historian
 /// is indeed nowhere to be found. Instead, the Elves discover an assortment
 /// of notes and lists of historically significant locations! This seems to be
@@ -41,22 +48,17 @@ use std::fs;
 /// add up the distances between all of the pairs you found.
 /// In the example above, this is `2 + 1 + 0 + 1 + 2 + 5`, a total distance of `11`!
 pub fn get_distance(input_string: String) -> u32 {
-    // Split into lines
-    let input_lines = input_string.lines();
-
-    // Creates binary heaps for left and right columns
-    let mut lnums: BinaryHeap<u32> = BinaryHeap::new();
-    let mut rnums: BinaryHeap<u32> = BinaryHeap::new();
-
-    // Inserts into both heaps, complexity is O(n*log(n)) where n is the line number
-    for line in input_lines {
-        let mut s = line.split_whitespace();
+    let (lnums, rnums) = parse_lists(&input_string, InputFormat::Whitespace, None).unwrap();
+    get_lists_distance(lnums, rnums)
+}
 
-        lnums.push(s.next().unwrap().parse().unwrap());
-        rnums.push(s.next().unwrap().parse().unwrap());
-    }
+/// Same as [`get_distance`] for lists already read from the input
+pub fn get_lists_distance(lnums: Vec<u32>, rnums: Vec<u32>) -> u32 {
+    // Creates binary heaps for left and right columns, complexity is O(n)
+    let lnums: BinaryHeap<u32> = BinaryHeap::from(lnums);
+    let rnums: BinaryHeap<u32> = BinaryHeap::from(rnums);
 
-    // Collect heap into a vector, complexity is O(n)
+    // Collect heap into a vector, complexity is O(n*log(n)) where n is the line number
     let lnums = lnums.into_sorted_vec();
     let rnums = rnums.into_sorted_vec();
 
@@ -93,18 +95,17 @@ pub fn get_distance(input_string: String) -> u32 {
 ///
 /// Once again consider your left and right lists. What is their similarity score?
 pub fn get_similarity(input_string: String) -> u32 {
-    let input_lines = input_string.lines();
+    let (lnums, rnums) = parse_lists(&input_string, InputFormat::Whitespace, None).unwrap();
+    get_lists_similarity(&lnums, &rnums)
+}
 
+/// Same as [`get_similarity`] for lists already read from the input
+pub fn get_lists_similarity(lnums: &[u32], rnums: &[u32]) -> u32 {
     let mut lmap: HashMap<u32, u8> = HashMap::new();
     let mut rmap: HashMap<u32, u8> = HashMap::new();
 
     // Map the numbers to their count in the input
-    for line in input_lines {
-        let mut s = line.split_whitespace();
-
-        let lnum: u32 = s.next().unwrap().parse().unwrap();
-        let rnum: u32 = s.next().unwrap().parse().unwrap();
-
+    for (&lnum, &rnum) in lnums.iter().zip(rnums) {
         lmap.entry(lnum).and_modify(|e| *e += 1).or_insert(1);
         rmap.entry(rnum).and_modify(|e| *e += 1).or_insert(1);
     }
@@ -117,11 +118,86 @@ pub fn get_similarity(input_string: String) -> u32 {
     similarity
 }
 
+/// Applies a list of updates to the location lists and prints the distance
+/// and similarity after each one. Every update is a pair of IDs like in the
+/// input, prefixed with `+` to add it or `-` to remove it, e.g. `+ 3 4`.
+fn apply_updates(lnums: &[u32], rnums: &[u32], updates_string: &str) {
+    let mut distance = DistanceIndex::new();
+    let mut similarity = SimilarityIndex::new();
+
+    for (&l, &r) in lnums.iter().zip(rnums) {
+        distance.insert_pair(l, r);
+        similarity.insert_left(l);
+        similarity.insert_right(r);
+    }
+
+    for line in updates_string.lines() {
+        let mut s = line.split_whitespace();
+        let op = s.next().unwrap();
+        let l: u32 = s.next().unwrap().parse().unwrap();
+        let r: u32 = s.next().unwrap().parse().unwrap();
+
+        match op {
+            "+" => {
+                distance.insert_pair(l, r);
+                similarity.insert_left(l);
+                similarity.insert_right(r);
+            }
+            "-" => {
+                if !distance.remove_pair(l, r) {
+                    println!("{line}: pair not found");
+                    continue;
+                }
+                similarity.remove_left(l);
+                similarity.remove_right(r);
+            }
+            _ => panic!("Unknown update: {line}"),
+        }
+        println!(
+            "{line}: distance: {}, similarity: {}",
+            distance.distance(),
+            similarity.similarity()
+        );
+    }
+}
+
+/// Usage: `advent1 [--input FILE] [--format auto|whitespace|csv|tsv|json]
+/// [--columns LEFT,RIGHT] [--updates FILE]`
 fn main() {
+    let mut input_path = String::from("src/input.txt");
+    let mut format = InputFormat::Auto;
+    let mut columns: Option<Columns> = None;
+    let mut updates_path: Option<String> = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| panic!("Missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--input" => input_path = value(),
+            "--format" => format = value().parse().unwrap(),
+            "--columns" => columns = Some(value().parse().unwrap()),
+            "--updates" => updates_path = Some(value()),
+            _ => panic!("Unknown argument: {arg}"),
+        }
+    }
+
     // Read from input file
-    let input = fs::read_to_string("src/input.txt").expect("Could not read the puzzle input");
-    println!("distance: {}", get_distance(input.clone()));
-    println!("similarity: {}", get_similarity(input.clone()));
+    let input = fs::read_to_string(input_path).expect("Could not read the puzzle input");
+    let (lnums, rnums) = parse_lists(&input, format, columns.as_ref()).unwrap();
+    println!(
+        "distance: {}",
+        get_lists_distance(lnums.clone(), rnums.clone())
+    );
+    println!("similarity: {}", get_lists_similarity(&lnums, &rnums));
+
+    // Optionally replay a file of updates on top of the input
+    if let Some(path) = updates_path {
+        let updates = fs::read_to_string(path).expect("Could not read the updates");
+        apply_updates(&lnums, &rnums, &updates);
+    }
 }
 
 #[cfg(test)]
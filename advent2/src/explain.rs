@@ -0,0 +1,200 @@
+use crate::parse::{parse_report, Level, ParseError};
+use crate::policy::{Direction, SafetyPolicy};
+use crate::safety::{dampen, first_violation, Violation};
+
+/// Everything there is to know about why a report is safe or not
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    /// Line of the report in the input, starting from 1
+    pub line: usize,
+    pub levels: Vec<Level>,
+    /// `None` for a report safe as it is
+    pub violation: Option<Violation>,
+    /// Index of the level removed by the Problem Dampener
+    pub removed: Option<usize>,
+    pub safe_with_dampener: bool,
+}
+
+/// Finds the first step breaking the policy.
+///
+/// When both directions are allowed, the report is expected to go the way of its
+/// first step that is not flat: `1 3 2` changes direction at `3 2`, while `3 1 2`
+/// changes direction at `1 2`. A report has no violation exactly when
+/// [`crate::is_line_safe`] says it is safe.
+pub fn find_violation(nums: &[Level], policy: &SafetyPolicy) -> Option<Violation> {
+    let increasing = match policy.direction {
+        Direction::Increasing => true,
+        Direction::Decreasing => false,
+        Direction::Either => nums
+            .iter()
+            .zip(nums.iter().skip(1))
+            .find(|(a, b)| a != b)
+            .is_some_and(|(a, b)| a < b),
+    };
+
+    first_violation(nums, policy, increasing, None)
+}
+
+pub fn explain_line(
+    line_number: usize,
+    line: &str,
+    policy: &SafetyPolicy,
+) -> Result<Explanation, ParseError> {
+    let levels = parse_report(line_number, line)?;
+
+    let violation = find_violation(&levels, policy);
+    let dampened = dampen(&levels, policy);
+
+    Ok(Explanation {
+        line: line_number,
+        violation,
+        removed: dampened.flatten(),
+        safe_with_dampener: dampened.is_some(),
+        levels,
+    })
+}
+
+pub fn explain(input_levels: &str, policy: &SafetyPolicy) -> Result<Vec<Explanation>, ParseError> {
+    input_levels
+        .lines()
+        .enumerate()
+        .map(|(i, line)| explain_line(i + 1, line, policy))
+        .collect()
+}
+
+impl Explanation {
+    /// The report with the violating pair in brackets and the removed level
+    /// in parentheses, followed by what happened to it, e.g.
+    /// `1 [3 2] 4 5: safe with dampener, removed index 1 (3), direction change at index 1`
+    pub fn annotate(&self) -> String {
+        let levels: Vec<String> = self
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(i, level)| {
+                let mut s = level.to_string();
+                if self.removed == Some(i) {
+                    s = format!("({s})");
+                }
+                match self.violation {
+                    Some(v) if v.index == i => format!("[{s}"),
+                    Some(v) if v.next == i => format!("{s}]"),
+                    _ => s,
+                }
+            })
+            .collect();
+
+        let status = match (self.violation, self.removed) {
+            (None, _) => "safe".to_string(),
+            (Some(_), Some(removed)) => format!(
+                "safe with dampener, removed index {removed} ({})",
+                self.levels[removed]
+            ),
+            _ => "unsafe".to_string(),
+        };
+        let reason = match self.violation {
+            Some(v) => format!(", {} at index {}", v.kind, v.index),
+            None => String::new(),
+        };
+
+        format!("{}: {status}{reason}", levels.join(" "))
+    }
+
+    /// One line of JSON, e.g. `{"line":2,"levels":[1,2,7,8,9],"safe":false,
+    /// "safe_with_dampener":false,"violation":{"index":1,"values":[2,7],
+    /// "kind":"step_too_large"},"removed":null}`
+    pub fn to_json(&self) -> String {
+        let levels: Vec<String> = self.levels.iter().map(|l| l.to_string()).collect();
+        let violation = match self.violation {
+            Some(v) => format!(
+                "{{\"index\":{},\"values\":[{},{}],\"kind\":\"{}\"}}",
+                v.index,
+                v.values.0,
+                v.values.1,
+                v.kind.name()
+            ),
+            None => "null".to_string(),
+        };
+        let removed = match self.removed {
+            Some(i) => i.to_string(),
+            None => "null".to_string(),
+        };
+
+        format!(
+            "{{\"line\":{},\"levels\":[{}],\"safe\":{},\"safe_with_dampener\":{},\"violation\":{},\"removed\":{}}}",
+            self.line,
+            levels.join(","),
+            self.violation.is_none(),
+            self.safe_with_dampener,
+            violation,
+            removed
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn explains_the_example() {
+        let input: String =
+            fs::read_to_string("src/test_input.txt").expect("Could not read the puzzle input");
+        let listing: Vec<String> = explain(&input, &SafetyPolicy::default())
+            .unwrap()
+            .iter()
+            .map(|e| e.annotate())
+            .collect();
+
+        assert_eq!(
+            vec![
+                "7 6 4 2 1: safe",
+                "1 [2 7] 8 9: unsafe, step too large at index 1",
+                "9 7 [6 2] 1: unsafe, step too large at index 2",
+                "1 [(3) 2] 4 5: safe with dampener, removed index 1 (3), direction change at index 1",
+                "8 6 [(4) 4] 1: safe with dampener, removed index 2 (4), flat at index 2",
+                "1 3 6 7 9: safe",
+            ],
+            listing
+        );
+    }
+
+    #[test]
+    fn violation_agrees_with_is_line_safe() {
+        let policies = [
+            SafetyPolicy::default(),
+            SafetyPolicy {
+                allow_flat: true,
+                ..SafetyPolicy::default()
+            },
+            SafetyPolicy {
+                min_step: 2,
+                direction: Direction::Increasing,
+                ..SafetyPolicy::default()
+            },
+        ];
+        let lines = ["3 3 5 4", "3 3 4 5", "5 5 5", "4 2 3", "1 3 5 4", "", "7"];
+
+        for policy in &policies {
+            for line in lines {
+                let nums = parse_report(1, line).unwrap();
+                assert_eq!(
+                    crate::is_line_safe(&nums, policy),
+                    find_violation(&nums, policy).is_none(),
+                    "{line} with {policy:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn exports_json() {
+        let e = explain_line(2, "1 2 7 8 9", &SafetyPolicy::default()).unwrap();
+        assert_eq!(
+            "{\"line\":2,\"levels\":[1,2,7,8,9],\"safe\":false,\"safe_with_dampener\":false,\
+             \"violation\":{\"index\":1,\"values\":[2,7],\"kind\":\"step_too_large\"},\"removed\":null}",
+            e.to_json()
+        );
+    }
+}
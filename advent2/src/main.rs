@@ -1,34 +1,30 @@
+use std::env;
 use std::fs;
+use std::io;
+use std::process;
+use std::thread;
+use std::time::Instant;
 
-const ADMISSIBLE_INCREMENT: u8 = 3;
+mod explain;
+mod monitor;
+mod parallel;
+mod parse;
+mod policy;
+mod repair;
+mod safety;
+mod stats;
 
-fn is_line_safe(line: &str) -> bool {
-    let mut nums: Vec<u8> = Vec::new();
+use parse::{parse_report, Level, ParseError};
+use policy::SafetyPolicy;
+use safety::{dampen, first_violation};
 
-    // Parse the numbers
-    line.split_whitespace()
-        .for_each(|n| nums.push(n.parse().unwrap()));
-
-    // Decreasing
-    let inc: Vec<bool> = nums
-        .iter()
-        .zip(nums.iter().skip(1))
-        .map(|(a, b)| b < a && a <= &(b + ADMISSIBLE_INCREMENT))
-        .collect();
-
-    // If it's decreasing, return and don't calculate the increasing
-    if inc.iter().all(|&x| x) {
-        return true;
-    }
-
-    // Increasing
-    let dec: Vec<bool> = nums
+fn is_line_safe(nums: &[Level], policy: &SafetyPolicy) -> bool {
+    // Check each allowed direction, stopping at the first one that fits
+    policy
+        .direction
+        .candidates()
         .iter()
-        .zip(nums.iter().skip(1))
-        .map(|(a, b)| a < b && &(a + ADMISSIBLE_INCREMENT) >= b)
-        .collect();
-
-    dec.iter().all(|&x| x)
+        .any(|&increasing| first_violation(nums, policy, increasing, None).is_none())
 }
 
 /// Fortunately, the first location The Historians want to search
@@ -75,63 +71,19 @@ fn is_line_safe(line: &str) -> bool {
 /// So, in this example, `2` reports are safe.
 ///
 /// Analyze the unusual data from the engineers. How many reports are safe?
-pub fn count_safe_levels(input_levels: String) -> u32 {
+pub fn count_safe_levels(input_levels: String, policy: &SafetyPolicy) -> Result<u32, ParseError> {
     let mut counter: u32 = 0;
     let input_lines = input_levels.lines();
-    for line in input_lines {
-        if is_line_safe(line) {
+    for (i, line) in input_lines.enumerate() {
+        if is_line_safe(&parse_report(i + 1, line)?, policy) {
             counter += 1;
         }
     }
-    counter
+    Ok(counter)
 }
 
-fn is_line_safe_with_dampener(line: &str) -> bool {
-    let mut nums: Vec<u8> = Vec::new();
-
-    // Parse the numbers
-    line.split_whitespace()
-        .for_each(|n| nums.push(n.parse().unwrap()));
-
-    // Decreasing
-    for (i, _) in nums.iter().enumerate() {
-        let nums_cut: Vec<u8> = nums
-            .iter()
-            .enumerate()
-            .filter(|(idx, _)| *idx != i)
-            .map(|(_, value)| *value)
-            .collect();
-        let inc: Vec<bool> = nums_cut
-            .iter()
-            .zip(nums_cut.iter().skip(1))
-            .map(|(a, b)| b < a && a <= &(b + ADMISSIBLE_INCREMENT))
-            .collect();
-
-        if inc.iter().all(|&x| x) {
-            return true;
-        }
-    }
-
-    // Increasing
-    for (i, _) in nums.iter().enumerate() {
-        let nums_cut: Vec<u8> = nums
-            .iter()
-            .enumerate()
-            .filter(|(idx, _)| *idx != i)
-            .map(|(_, value)| *value)
-            .collect();
-        let dec: Vec<bool> = nums_cut
-            .iter()
-            .zip(nums_cut.iter().skip(1))
-            .map(|(a, b)| a < b && &(a + ADMISSIBLE_INCREMENT) >= b)
-            .collect();
-
-        if dec.iter().all(|&x| x) {
-            return true;
-        }
-    }
-
-    false
+fn is_line_safe_with_dampener(nums: &[Level], policy: &SafetyPolicy) -> bool {
+    dampen(nums, policy).is_some()
 }
 
 /// The engineers are surprised by the low number of safe reports
@@ -157,37 +109,246 @@ fn is_line_safe_with_dampener(line: &str) -> bool {
 /// Update your analysis by handling situations where the
 /// Problem Dampener can remove a single level from unsafe reports.
 /// How many reports are now safe?
-pub fn count_safe_levels_with_dampener(input_levels: String) -> u32 {
+pub fn count_safe_levels_with_dampener(
+    input_levels: String,
+    policy: &SafetyPolicy,
+) -> Result<u32, ParseError> {
     let mut counter: u32 = 0;
     let input_lines = input_levels.lines();
-    for line in input_lines {
-        if is_line_safe_with_dampener(line) {
+    for (i, line) in input_lines.enumerate() {
+        if is_line_safe_with_dampener(&parse_report(i + 1, line)?, policy) {
             counter += 1;
         }
     }
-    counter
+    Ok(counter)
+}
+
+/// Minimum number of levels to remove from the report to make it safe.
+///
+/// The levels that are kept form a subsequence of the report in which every
+/// two consecutive levels make a safe step, so this is the length of the report
+/// minus the longest such subsequence. `longest[j]` is the longest one ending
+/// with the level `j`, found from every `longest[i]` before it, O(n^2).
+pub fn min_removals(report: &[Level], policy: &SafetyPolicy) -> usize {
+    let mut best = 0;
+
+    for &increasing in policy.direction.candidates() {
+        let mut longest: Vec<usize> = vec![1; report.len()];
+        for j in 0..report.len() {
+            for i in 0..j {
+                if policy.is_step_safe(report[i], report[j], increasing) {
+                    longest[j] = longest[j].max(longest[i] + 1);
+                }
+            }
+        }
+        best = best.max(longest.into_iter().max().unwrap_or(0));
+    }
+
+    report.len() - best
+}
+
+/// Number of reports needing exactly `k` removals to be safe, indexed by `k`.
+/// Summing the first `k + 1` entries gives the number of reports that are safe
+/// with a Problem Dampener allowed to remove up to `k` levels.
+pub fn count_by_min_removals(
+    input_levels: String,
+    policy: &SafetyPolicy,
+) -> Result<Vec<u32>, ParseError> {
+    let mut histogram: Vec<u32> = Vec::new();
+    for (i, line) in input_levels.lines().enumerate() {
+        let k = min_removals(&parse_report(i + 1, line)?, policy);
+
+        if histogram.len() <= k {
+            histogram.resize(k + 1, 0);
+        }
+        histogram[k] += 1;
+    }
+    Ok(histogram)
+}
+
+/// Prints the error of an invalid input and stops
+fn exit_on_error<T>(result: Result<T, ParseError>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("Invalid input: {e}");
+        process::exit(1);
+    })
 }
 
+/// Usage: `advent2 [--min-step N] [--max-step N] [--allow-flat]
+/// [--direction increasing|decreasing|either] [--histogram] [--explain] [--explain-json]
+/// [--monitor] [--budget K] [--input FILE] [--threads N] [--repair] [--stats]`
+///
+/// With `--monitor`, the reports are read from the standard input as they come
+/// instead of from the puzzle input, with a dampener removing up to `K` levels.
+/// Only the options of the policy go along with it.
+/// With `--threads`, the safe reports are counted on `N` threads (0 for one per
+/// core) and the throughput is printed too, the other options working as usual.
+/// With `--repair`, every unsafe report is printed along with the smallest edit
+/// making it safe. With `--stats`, a summary of the reports is printed after
+/// the counts.
 fn main() {
-    let input: String =
-        fs::read_to_string("src/input.txt").expect("Could not read the puzzle input");
-    println!("safe: {}", count_safe_levels(input.clone()));
-    println!(
-        "safe with dampener: {}",
-        count_safe_levels_with_dampener(input.clone())
-    );
+    let mut policy = SafetyPolicy::default();
+    let mut input_path: Option<String> = None;
+    let mut threads: Option<usize> = None;
+    let mut monitor = false;
+    let mut budget: Option<usize> = None;
+    let mut histogram = false;
+    let mut explain = false;
+    let mut explain_json = false;
+    let mut repair = false;
+    let mut stats = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| panic!("Missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--min-step" => policy.min_step = value().parse().unwrap(),
+            "--max-step" => policy.max_step = value().parse().unwrap(),
+            "--allow-flat" => policy.allow_flat = true,
+            "--direction" => policy.direction = value().parse().unwrap(),
+            "--histogram" => histogram = true,
+            "--explain" => explain = true,
+            "--explain-json" => explain_json = true,
+            "--repair" => repair = true,
+            "--stats" => stats = true,
+            "--monitor" => monitor = true,
+            "--budget" => budget = Some(value().parse().unwrap()),
+            "--input" => input_path = Some(value()),
+            "--threads" => threads = Some(value().parse().unwrap()),
+            _ => panic!("Unknown argument: {arg}"),
+        }
+    }
+
+    let unused_with_monitor = [
+        ("--input", input_path.is_some()),
+        ("--threads", threads.is_some()),
+        ("--explain", explain),
+        ("--explain-json", explain_json),
+        ("--repair", repair),
+        ("--stats", stats),
+        ("--histogram", histogram),
+    ];
+    for (name, given) in unused_with_monitor {
+        if monitor && given {
+            panic!("{name} has no effect with --monitor");
+        }
+    }
+    if !monitor && budget.is_some() {
+        panic!("--budget only goes with --monitor");
+    }
+
+    if monitor {
+        let budget = budget.unwrap_or(1);
+        let (safe, safe_with_dampener) =
+            monitor::monitor_stream(io::stdin(), &mut io::stdout(), &policy, budget)
+                .unwrap_or_else(|e| {
+                    eprintln!("Invalid input: {e}");
+                    process::exit(1);
+                });
+        println!("safe: {safe}");
+        println!("safe with dampener: {safe_with_dampener}");
+        return;
+    }
+
+    let input_path = input_path.unwrap_or_else(|| String::from("src/input.txt"));
+    let input: String = fs::read_to_string(input_path).expect("Could not read the puzzle input");
+
+    // The JSON lines are meant for other tools, so they come alone
+    if explain_json {
+        for e in exit_on_error(explain::explain(&input, &policy)) {
+            println!("{}", e.to_json());
+        }
+        return;
+    }
+    if explain {
+        for e in exit_on_error(explain::explain(&input, &policy)) {
+            println!("{:>4}: {}", e.line, e.annotate());
+        }
+    }
+
+    if repair {
+        for (i, line) in input.lines().enumerate() {
+            let nums = exit_on_error(parse_report(i + 1, line));
+            if is_line_safe(&nums, &policy) {
+                continue;
+            }
+            match repair::repair(&nums, &policy) {
+                Some(r) => {
+                    let levels: Vec<String> = r.levels.iter().map(|l| l.to_string()).collect();
+                    println!("{:>4}: {line} -> {}, {}", i + 1, levels.join(" "), r.edit);
+                }
+                None => println!("{:>4}: {line} -> no single edit makes it safe", i + 1),
+            }
+        }
+    }
+
+    match threads {
+        Some(threads) => {
+            let threads = match threads {
+                0 => thread::available_parallelism().map_or(1, |n| n.get()),
+                n => n,
+            };
+            let start = Instant::now();
+            let counts = exit_on_error(parallel::count_safe_parallel(&input, &policy, threads));
+            let elapsed = start.elapsed().as_secs_f64();
+
+            println!("safe: {}", counts.safe);
+            println!("safe with dampener: {}", counts.safe_with_dampener);
+            println!(
+                "{} reports on {threads} threads in {:.3} s: {:.0} reports/s, {:.1} MB/s",
+                counts.reports,
+                elapsed,
+                counts.reports as f64 / elapsed,
+                input.len() as f64 / elapsed / 1e6
+            );
+        }
+        None => {
+            let safe = exit_on_error(count_safe_levels(input.clone(), &policy));
+            let safe_with_dampener =
+                exit_on_error(count_safe_levels_with_dampener(input.clone(), &policy));
+            println!("safe: {safe}");
+            println!("safe with dampener: {safe_with_dampener}");
+        }
+    }
+
+    if stats {
+        println!("{}", exit_on_error(stats::summarize(&input, &policy)));
+    }
+
+    if histogram {
+        let mut safe = 0;
+        let counts = exit_on_error(count_by_min_removals(input, &policy));
+        for (k, count) in counts.iter().enumerate() {
+            safe += count;
+            println!("{k} removals: {count} reports, safe with up to {k}: {safe}");
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use policy::Direction;
+
+    /// A xorshift generator of numbers below `n`, for the randomized tests
+    pub fn xorshift(mut seed: u32) -> impl FnMut(u32) -> u32 {
+        move |n| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed % n
+        }
+    }
 
     #[test]
     fn given_test_part_one() {
         let input: String =
             fs::read_to_string("src/test_input.txt").expect("Could not read the puzzle input");
         let e = 2;
-        assert_eq!(e, count_safe_levels(input))
+        assert_eq!(Ok(e), count_safe_levels(input, &SafetyPolicy::default()))
     }
 
     #[test]
@@ -195,6 +356,133 @@ mod tests {
         let input: String =
             fs::read_to_string("src/test_input.txt").expect("Could not read the puzzle input");
         let e = 4;
-        assert_eq!(e, count_safe_levels_with_dampener(input))
+        assert_eq!(
+            Ok(e),
+            count_safe_levels_with_dampener(input, &SafetyPolicy::default())
+        )
+    }
+
+    fn is_line_safe_with_dampener_brute_force(nums: &[Level], policy: &SafetyPolicy) -> bool {
+        for &increasing in policy.direction.candidates() {
+            for i in 0..nums.len() {
+                let mut nums_cut = nums.to_vec();
+                nums_cut.remove(i);
+                if first_violation(&nums_cut, policy, increasing, None).is_none() {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    #[test]
+    fn dampener_matches_brute_force() {
+        let mut next = xorshift(2024);
+
+        let policies = [
+            SafetyPolicy::default(),
+            SafetyPolicy {
+                allow_flat: true,
+                ..SafetyPolicy::default()
+            },
+            SafetyPolicy {
+                min_step: 2,
+                max_step: 4,
+                direction: Direction::Decreasing,
+                ..SafetyPolicy::default()
+            },
+        ];
+
+        for _ in 0..20000 {
+            // Mostly small steps so that a good share of the reports are almost safe
+            let len = 1 + next(8);
+            let mut level: Level = next(10) as Level - 5;
+            let mut nums: Vec<Level> = Vec::new();
+            for _ in 0..len {
+                nums.push(level);
+                level += next(9) as Level - 4;
+            }
+
+            for policy in &policies {
+                let safe = is_line_safe_with_dampener(&nums, policy);
+                assert_eq!(
+                    is_line_safe_with_dampener_brute_force(&nums, policy),
+                    safe,
+                    "{nums:?} with {policy:?}"
+                );
+                assert_eq!(
+                    safe,
+                    min_removals(&nums, policy) <= 1,
+                    "{nums:?} with {policy:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn counts_min_removals() {
+        let policy = SafetyPolicy::default();
+        assert_eq!(2, min_removals(&[1, 2, 7, 8, 9], &policy));
+        assert_eq!(1, min_removals(&[8, 6, 4, 4, 1], &policy));
+        assert_eq!(3, min_removals(&[1, 9, 2, 8, 3, 7], &policy));
+        assert_eq!(0, min_removals(&[], &policy));
+
+        let input: String =
+            fs::read_to_string("src/test_input.txt").expect("Could not read the puzzle input");
+        assert_eq!(Ok(vec![2, 2, 2]), count_by_min_removals(input, &policy));
+    }
+
+    #[test]
+    fn counts_under_other_policies() {
+        let input: String =
+            fs::read_to_string("src/test_input.txt").expect("Could not read the puzzle input");
+        let count = |policy: SafetyPolicy| {
+            (
+                count_safe_levels(input.clone(), &policy).unwrap(),
+                count_safe_levels_with_dampener(input.clone(), &policy).unwrap(),
+            )
+        };
+        let default = SafetyPolicy::default();
+
+        // `9 7 6 2 1` now fits, only `1 2 7 8 9` is left out with the dampener
+        let wider = SafetyPolicy {
+            max_step: 4,
+            ..default
+        };
+        assert_eq!((3, 5), count(wider));
+
+        // Only `1 3 6 7 9` is left, and `1 3 2 4 5` with the dampener
+        let increasing = SafetyPolicy {
+            direction: Direction::Increasing,
+            ..default
+        };
+        assert_eq!((1, 2), count(increasing));
+
+        // `8 6 4 4 1` no longer needs the dampener
+        let flat = SafetyPolicy {
+            allow_flat: true,
+            ..default
+        };
+        assert_eq!((3, 4), count(flat));
+
+        // Steps of 1 are too small, only removing the `7` of `1 3 6 7 9`
+        // or a `4` of `8 6 4 4 1` gets rid of them
+        let strict = SafetyPolicy {
+            min_step: 2,
+            ..default
+        };
+        assert_eq!((0, 2), count(strict));
+    }
+
+    #[test]
+    fn handles_levels_beyond_u8() {
+        let input = "250 252 255 258\n-3 -1 0 2\n9223372036854775807 9223372036854775806\n\
+                     -9223372036854775808 9223372036854775807\n"
+            .to_string();
+        assert_eq!(Ok(3), count_safe_levels(input, &SafetyPolicy::default()));
+
+        let err = count_safe_levels("1 2 3\n4 five 6\n".to_string(), &SafetyPolicy::default())
+            .unwrap_err();
+        assert_eq!((2, 3), (err.line, err.column));
     }
 }
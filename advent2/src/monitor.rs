@@ -0,0 +1,313 @@
+use std::error::Error;
+use std::io::{BufReader, Read, Write};
+
+use crate::parse::{parse_level, Level};
+use crate::policy::SafetyPolicy;
+
+/// One way of reading the report so far: the last level kept, the direction the
+/// report is expected to go and how many levels were removed to get there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Branch {
+    last: Option<Level>,
+    increasing: bool,
+    removed: usize,
+}
+
+/// Checks a report one level at a time, as the levels come from the sensors.
+///
+/// The monitor follows every way of keeping the report safe by removing at most
+/// `budget` levels: each new level is either kept after the last kept one, or
+/// removed if the budget allows. A `budget` of 0 gives the rules of
+/// [`crate::is_line_safe`], a budget of 1 the ones of the Problem Dampener.
+/// There are at most `budget + 1` candidates for the last kept level, so the
+/// monitor works in constant memory whatever the length of the report.
+#[derive(Debug, Clone)]
+pub struct SafetyMonitor {
+    policy: SafetyPolicy,
+    budget: usize,
+    branches: Vec<Branch>,
+    len: usize,
+}
+
+impl SafetyMonitor {
+    pub fn new(policy: SafetyPolicy, budget: usize) -> Self {
+        let mut monitor = SafetyMonitor {
+            policy,
+            budget,
+            branches: Vec::new(),
+            len: 0,
+        };
+        monitor.reset();
+        monitor
+    }
+
+    /// Starts a new report
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.branches = self
+            .policy
+            .direction
+            .candidates()
+            .iter()
+            .map(|&increasing| Branch {
+                last: None,
+                increasing,
+                removed: 0,
+            })
+            .collect();
+    }
+
+    /// Feeds the next level of the report, returns whether it is still safe.
+    /// Once a report is unsafe, it stays unsafe until the next [`SafetyMonitor::reset`].
+    pub fn push(&mut self, level: Level) -> bool {
+        self.len += 1;
+
+        let mut branches: Vec<Branch> = Vec::new();
+        let mut add = |branch: Branch| {
+            if !branches.contains(&branch) {
+                branches.push(branch);
+            }
+        };
+        for branch in &self.branches {
+            let keep = match branch.last {
+                None => true,
+                Some(last) => self.policy.is_step_safe(last, level, branch.increasing),
+            };
+            if keep {
+                add(Branch {
+                    last: Some(level),
+                    ..*branch
+                });
+            }
+            if branch.removed < self.budget {
+                add(Branch {
+                    removed: branch.removed + 1,
+                    ..*branch
+                });
+            }
+        }
+
+        self.branches = branches;
+        self.is_safe()
+    }
+
+    pub fn is_safe(&self) -> bool {
+        !self.branches.is_empty()
+    }
+
+    /// Fewest levels removed so far to keep the report safe
+    pub fn removals(&self) -> Option<usize> {
+        self.branches.iter().map(|b| b.removed).min()
+    }
+
+    /// Number of levels fed since the start of the report
+    pub fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// Splits the incoming bytes into levels and reports, and feeds them to
+/// a monitor without the dampener and one with it
+struct StreamMonitor {
+    plain: SafetyMonitor,
+    dampened: SafetyMonitor,
+    safe: u32,
+    safe_with_dampener: u32,
+    token: Vec<u8>,
+    line: usize,
+    column: usize,
+    token_column: usize,
+}
+
+impl StreamMonitor {
+    fn feed<W: Write>(&mut self, byte: u8, out: &mut W) -> Result<(), Box<dyn Error>> {
+        // Continuation bytes of UTF-8 characters do not start a new column
+        if byte & 0xC0 != 0x80 {
+            self.column += 1;
+        }
+
+        if !byte.is_ascii_whitespace() {
+            if self.token.is_empty() {
+                self.token_column = self.column;
+            }
+            self.token.push(byte);
+            return Ok(());
+        }
+
+        if !self.token.is_empty() {
+            let token = String::from_utf8_lossy(&self.token).to_string();
+            let level = parse_level(self.line, self.token_column, &token)?;
+            self.token.clear();
+
+            // A monitor stops counting levels once unsafe, so each gives its own index
+            let line = self.line;
+            let index = self.plain.len();
+            if self.plain.is_safe() && !self.plain.push(level) {
+                writeln!(out, "report {line}: unsafe at index {index} ({level})")?;
+            }
+            let index = self.dampened.len();
+            if self.dampened.is_safe() && !self.dampened.push(level) {
+                writeln!(
+                    out,
+                    "report {line}: unsafe with dampener at index {index} ({level})"
+                )?;
+            }
+            out.flush()?;
+        }
+
+        if byte == b'\n' {
+            let status = match (self.plain.is_safe(), self.dampened.removals()) {
+                (true, _) => "safe".to_string(),
+                (false, Some(removed)) => format!("safe with dampener, {removed} removed"),
+                (false, None) => "unsafe".to_string(),
+            };
+            writeln!(out, "report {}: {status}", self.line)?;
+            out.flush()?;
+
+            self.safe += self.plain.is_safe() as u32;
+            self.safe_with_dampener += self.dampened.is_safe() as u32;
+            self.plain.reset();
+            self.dampened.reset();
+            self.line += 1;
+            self.column = 0;
+        }
+        Ok(())
+    }
+}
+
+/// Reads levels from `reader` as soon as they arrive, a new line starting a new
+/// report, and writes to `out` the moment a report becomes unsafe, both without
+/// and with a dampener removing up to `budget` levels. Each report ends with a
+/// line giving its status. Returns the number of safe reports without and with
+/// the dampener.
+pub fn monitor_stream<R: Read, W: Write>(
+    reader: R,
+    out: &mut W,
+    policy: &SafetyPolicy,
+    budget: usize,
+) -> Result<(u32, u32), Box<dyn Error>> {
+    let mut stream = StreamMonitor {
+        plain: SafetyMonitor::new(*policy, 0),
+        dampened: SafetyMonitor::new(*policy, budget),
+        safe: 0,
+        safe_with_dampener: 0,
+        token: Vec::new(),
+        line: 1,
+        column: 0,
+        token_column: 0,
+    };
+
+    for byte in BufReader::new(reader).bytes() {
+        stream.feed(byte?, out)?;
+    }
+    // The last report may not end with a new line
+    if !stream.token.is_empty() || stream.plain.len() > 0 {
+        stream.feed(b'\n', out)?;
+    }
+
+    Ok((stream.safe, stream.safe_with_dampener))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_report;
+    use crate::policy::Direction;
+    use crate::{is_line_safe, is_line_safe_with_dampener, min_removals};
+
+    #[test]
+    fn agrees_with_the_line_checks() {
+        let mut next = crate::tests::xorshift(33);
+        let policies = [
+            SafetyPolicy::default(),
+            SafetyPolicy {
+                allow_flat: true,
+                direction: Direction::Increasing,
+                ..SafetyPolicy::default()
+            },
+        ];
+
+        for _ in 0..5000 {
+            let len = next(9);
+            let mut level: Level = 0;
+            let mut nums: Vec<Level> = Vec::new();
+            for _ in 0..len {
+                nums.push(level);
+                level += next(9) as Level - 4;
+            }
+
+            for policy in &policies {
+                for budget in 0..4 {
+                    let mut monitor = SafetyMonitor::new(*policy, budget);
+                    let safe = nums.iter().all(|&level| monitor.push(level));
+                    assert_eq!(
+                        min_removals(&nums, policy) <= budget,
+                        safe,
+                        "{nums:?} with {policy:?}, budget {budget}"
+                    );
+                    match budget {
+                        0 => assert_eq!(is_line_safe(&nums, policy), safe),
+                        1 => assert_eq!(is_line_safe_with_dampener(&nums, policy), safe),
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reports_the_moment_it_becomes_unsafe() {
+        let input = "7 6 4 2 1\n1 2 7 8 9\n1 3 2 4 5\n8 6 4 4 1";
+        let mut out: Vec<u8> = Vec::new();
+        let counts =
+            monitor_stream(input.as_bytes(), &mut out, &SafetyPolicy::default(), 1).unwrap();
+
+        assert_eq!((1, 3), counts);
+        assert_eq!(
+            "report 1: safe\n\
+             report 2: unsafe at index 2 (7)\n\
+             report 2: unsafe with dampener at index 3 (8)\n\
+             report 2: unsafe\n\
+             report 3: unsafe at index 2 (2)\n\
+             report 3: safe with dampener, 1 removed\n\
+             report 4: unsafe at index 3 (4)\n\
+             report 4: safe with dampener, 1 removed\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn locates_each_failure_on_its_own() {
+        // Without the dampener, 5 is too far from 1; with it, 20 is too far from 7
+        let mut out: Vec<u8> = Vec::new();
+        monitor_stream(
+            "1 5 6 7 20\n".as_bytes(),
+            &mut out,
+            &SafetyPolicy::default(),
+            1,
+        )
+        .unwrap();
+        assert_eq!(
+            "report 1: unsafe at index 1 (5)\n\
+             report 1: unsafe with dampener at index 4 (20)\n\
+             report 1: unsafe\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn locates_bad_levels_in_the_stream() {
+        let mut out: Vec<u8> = Vec::new();
+        let err = monitor_stream(
+            "1 2 3\n4 5 x6\n".as_bytes(),
+            &mut out,
+            &SafetyPolicy::default(),
+            1,
+        )
+        .unwrap_err();
+        assert_eq!(
+            parse_report(2, "4 5 x6").unwrap_err().to_string(),
+            err.to_string()
+        );
+    }
+}
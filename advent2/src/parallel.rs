@@ -0,0 +1,136 @@
+use std::thread;
+
+use crate::parse::{parse_report, ParseError};
+use crate::policy::SafetyPolicy;
+use crate::{is_line_safe, is_line_safe_with_dampener};
+
+/// Totals over a set of reports
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub reports: u64,
+    pub safe: u64,
+    pub safe_with_dampener: u64,
+}
+
+impl Counts {
+    fn add(&mut self, other: Counts) {
+        self.reports += other.reports;
+        self.safe += other.safe;
+        self.safe_with_dampener += other.safe_with_dampener;
+    }
+}
+
+/// Splits the input into at most `parts` chunks of whole lines of about the same
+/// size, each with the number of the line it starts at
+fn split_lines(input: &str, parts: usize) -> Vec<(usize, &str)> {
+    let mut chunks: Vec<(usize, &str)> = Vec::new();
+    let target = input.len().div_ceil(parts.max(1)).max(1);
+    let (mut rest, mut line) = (input, 1);
+
+    while !rest.is_empty() {
+        // Cut right after the first new line past the target size
+        let end = match rest
+            .as_bytes()
+            .get(target..)
+            .and_then(|tail| tail.iter().position(|&b| b == b'\n'))
+        {
+            Some(i) => target + i + 1,
+            None => rest.len(),
+        };
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push((line, chunk));
+        line += chunk.bytes().filter(|&b| b == b'\n').count();
+        rest = tail;
+    }
+    chunks
+}
+
+fn count_chunk(
+    first_line: usize,
+    chunk: &str,
+    policy: &SafetyPolicy,
+) -> Result<Counts, ParseError> {
+    let mut counts = Counts::default();
+    for (i, line) in chunk.lines().enumerate() {
+        let nums = parse_report(first_line + i, line)?;
+        counts.reports += 1;
+        counts.safe += is_line_safe(&nums, policy) as u64;
+        counts.safe_with_dampener += is_line_safe_with_dampener(&nums, policy) as u64;
+    }
+    Ok(counts)
+}
+
+/// Counts the safe reports, with and without the Problem Dampener, on `threads`
+/// threads each taking a range of lines. The totals do not depend on the number
+/// of threads, and neither does the error: the one of the first invalid line.
+pub fn count_safe_parallel(
+    input_levels: &str,
+    policy: &SafetyPolicy,
+    threads: usize,
+) -> Result<Counts, ParseError> {
+    let chunks = split_lines(input_levels, threads);
+
+    let results: Vec<Result<Counts, ParseError>> = thread::scope(|s| {
+        let handles: Vec<_> = chunks
+            .iter()
+            .map(|&(first_line, chunk)| s.spawn(move || count_chunk(first_line, chunk, policy)))
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    // The chunks are in order, so the first error is the one of the first invalid line
+    let mut total = Counts::default();
+    for result in results {
+        total.add(result?);
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{count_safe_levels, count_safe_levels_with_dampener};
+    use std::fs;
+
+    #[test]
+    fn same_totals_whatever_the_threads() {
+        let input: String =
+            fs::read_to_string("src/test_input.txt").expect("Could not read the puzzle input");
+        let input = input.repeat(50);
+        let policy = SafetyPolicy::default();
+        let expected = Counts {
+            reports: input.lines().count() as u64,
+            safe: count_safe_levels(input.clone(), &policy).unwrap() as u64,
+            safe_with_dampener: count_safe_levels_with_dampener(input.clone(), &policy).unwrap()
+                as u64,
+        };
+
+        for threads in [1, 2, 3, 7, 64, 1000] {
+            assert_eq!(Ok(expected), count_safe_parallel(&input, &policy, threads));
+        }
+    }
+
+    #[test]
+    fn chunks_keep_whole_lines() {
+        let input = "1 2\n3 4 5\n\n6\n7 8 9 10\n11";
+        for parts in 1..12 {
+            let chunks = split_lines(input, parts);
+            assert!(chunks.len() <= parts);
+            assert_eq!(input, chunks.iter().map(|c| c.1).collect::<String>());
+            for (line, chunk) in chunks {
+                let first = input.lines().nth(line - 1).unwrap();
+                assert_eq!(Some(first), chunk.lines().next());
+            }
+        }
+        assert!(split_lines("", 4).is_empty());
+    }
+
+    #[test]
+    fn reports_the_first_invalid_line() {
+        let input = "1 2 3\n".repeat(100) + "1 x\n" + &"1 2 3\n".repeat(100) + "y\n";
+        for threads in [1, 4, 16] {
+            let err = count_safe_parallel(&input, &SafetyPolicy::default(), threads).unwrap_err();
+            assert_eq!((101, 3), (err.line, err.column));
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::fmt;
+use std::num::ParseIntError;
+
+/// A level reading. Sensors may report any value, negative ones included,
+/// so levels are wide and only ever compared through their differences.
+pub type Level = i64;
+
+/// A token of the input that is not a level
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Starting from 1
+    pub line: usize,
+    /// Starting from 1, in characters
+    pub column: usize,
+    pub token: String,
+    pub reason: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: `{}` is not a level ({})",
+            self.line, self.column, self.token, self.reason
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses a single level found at `line` and `column` of the input
+pub fn parse_level(line: usize, column: usize, token: &str) -> Result<Level, ParseError> {
+    token.parse().map_err(|e: ParseIntError| ParseError {
+        line,
+        column,
+        token: token.to_string(),
+        reason: e.to_string(),
+    })
+}
+
+/// Parses the levels of the report on line `line_number` of the input
+pub fn parse_report(line_number: usize, line: &str) -> Result<Vec<Level>, ParseError> {
+    line.split_whitespace()
+        .map(|token| match token.parse() {
+            Ok(level) => Ok(level),
+            Err(_) => {
+                // Only count the column on error, `token` is a slice of `line`
+                // so its offset is the difference of the pointers
+                let offset = token.as_ptr() as usize - line.as_ptr() as usize;
+                parse_level(line_number, line[..offset].chars().count() + 1, token)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_wide_and_negative_levels() {
+        assert_eq!(
+            Ok(vec![-3, 0, 300, Level::MAX]),
+            parse_report(1, " -3 0\t300 9223372036854775807 ")
+        );
+        assert_eq!(Ok(vec![]), parse_report(1, ""));
+    }
+
+    #[test]
+    fn reports_line_and_column() {
+        // Columns count characters, not bytes
+        let err = parse_report(3, "7\u{a0}\u{a0}8x 9").unwrap_err();
+        assert_eq!((3, 4, "8x"), (err.line, err.column, err.token.as_str()));
+        assert_eq!(
+            "line 3, column 4: `8x` is not a level (invalid digit found in string)",
+            err.to_string()
+        );
+
+        let err = parse_report(2, "1 99999999999999999999").unwrap_err();
+        assert_eq!((2, 3), (err.line, err.column));
+    }
+}
@@ -0,0 +1,127 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::parse::Level;
+
+const ADMISSIBLE_INCREMENT: u64 = 3;
+
+/// Which way the levels of a safe report are allowed to go
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Increasing,
+    Decreasing,
+    Either,
+}
+
+impl Direction {
+    /// The directions a report is checked against, `true` being increasing
+    pub fn candidates(&self) -> &'static [bool] {
+        match self {
+            Direction::Increasing => &[true],
+            Direction::Decreasing => &[false],
+            Direction::Either => &[false, true],
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "increasing" | "inc" => Ok(Direction::Increasing),
+            "decreasing" | "dec" => Ok(Direction::Decreasing),
+            "either" => Ok(Direction::Either),
+            _ => Err(format!("Unknown direction: {s}")),
+        }
+    }
+}
+
+/// Why a step between two adjacent levels is not safe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// The two levels are equal
+    Flat,
+    /// The step goes the other way than the report, or than the policy allows
+    DirectionChange,
+    StepTooSmall,
+    StepTooLarge,
+}
+
+impl ViolationKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ViolationKind::Flat => "flat",
+            ViolationKind::DirectionChange => "direction_change",
+            ViolationKind::StepTooSmall => "step_too_small",
+            ViolationKind::StepTooLarge => "step_too_large",
+        }
+    }
+}
+
+impl fmt::Display for ViolationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name().replace('_', " "))
+    }
+}
+
+/// The rules a report has to follow to be safe.
+///
+/// The default is the one of the Red-Nosed reactor: the levels are either all
+/// increasing or all decreasing, and two adjacent levels differ by 1 to 3.
+/// With `allow_flat`, two equal adjacent levels are also accepted
+/// whatever the direction and the steps are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SafetyPolicy {
+    pub min_step: u64,
+    pub max_step: u64,
+    pub allow_flat: bool,
+    pub direction: Direction,
+}
+
+impl Default for SafetyPolicy {
+    fn default() -> Self {
+        SafetyPolicy {
+            min_step: 1,
+            max_step: ADMISSIBLE_INCREMENT,
+            allow_flat: false,
+            direction: Direction::Either,
+        }
+    }
+}
+
+impl SafetyPolicy {
+    /// Whether going from level `a` to level `b` is fine in the given direction
+    pub fn is_step_safe(&self, a: Level, b: Level, increasing: bool) -> bool {
+        self.check_step(a, b, increasing).is_ok()
+    }
+
+    /// Same as [`SafetyPolicy::is_step_safe`], telling what is wrong with the step
+    pub fn check_step(&self, a: Level, b: Level, increasing: bool) -> Result<(), ViolationKind> {
+        // The difference of two levels always fits in the wider type
+        self.check_diff(b as i128 - a as i128, increasing)
+    }
+
+    /// Checks the signed difference between two adjacent levels. A decreasing
+    /// report is an increasing one upside down, so the difference is flipped
+    /// for it and both directions go through the same rules.
+    pub fn check_diff(&self, diff: i128, increasing: bool) -> Result<(), ViolationKind> {
+        let rise = if increasing { diff } else { -diff };
+
+        if rise == 0 {
+            if self.allow_flat {
+                Ok(())
+            } else {
+                Err(ViolationKind::Flat)
+            }
+        } else if rise < 0 {
+            Err(ViolationKind::DirectionChange)
+        } else if rise < self.min_step as i128 {
+            Err(ViolationKind::StepTooSmall)
+        } else if rise > self.max_step as i128 {
+            Err(ViolationKind::StepTooLarge)
+        } else {
+            Ok(())
+        }
+    }
+}
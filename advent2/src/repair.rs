@@ -0,0 +1,260 @@
+use std::fmt;
+
+use crate::parse::Level;
+use crate::policy::SafetyPolicy;
+use crate::safety::{dampen, first_violation};
+
+/// A single change to the levels of a report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    /// The level at this index is dropped, as the Problem Dampener does
+    Remove { index: usize, level: Level },
+    /// The level at this index is read as `to` instead of `from`
+    Substitute {
+        index: usize,
+        from: Level,
+        to: Level,
+    },
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Edit::Remove { index, level } => write!(f, "remove index {index} ({level})"),
+            Edit::Substitute { index, from, to } => {
+                write!(f, "replace index {index}: {from} -> {to}")
+            }
+        }
+    }
+}
+
+/// The smallest edit making an unsafe report safe, and the report once edited
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repair {
+    pub edit: Edit,
+    pub levels: Vec<Level>,
+}
+
+/// The values `x` for which the step from `a` to `x` is safe, as ranges
+fn fits_after(a: Level, policy: &SafetyPolicy, increasing: bool) -> Vec<(i128, i128)> {
+    let (lo, hi) = (policy.min_step.max(1) as i128, policy.max_step as i128);
+    let a = a as i128;
+    let mut ranges = Vec::new();
+    if lo <= hi {
+        ranges.push(if increasing {
+            (a + lo, a + hi)
+        } else {
+            (a - hi, a - lo)
+        });
+    }
+    if policy.allow_flat {
+        ranges.push((a, a));
+    }
+    ranges
+}
+
+/// The values `x` for which the step from `x` to `b` is safe, as ranges
+fn fits_before(b: Level, policy: &SafetyPolicy, increasing: bool) -> Vec<(i128, i128)> {
+    // Stepping up from `x` to `b` is stepping down from `b` to `x`
+    fits_after(b, policy, !increasing)
+}
+
+fn intersect(left: &[(i128, i128)], right: &[(i128, i128)]) -> Vec<(i128, i128)> {
+    let mut ranges = Vec::new();
+    for &(a, b) in left {
+        for &(c, d) in right {
+            if a.max(c) <= b.min(d) {
+                ranges.push((a.max(c), b.min(d)));
+            }
+        }
+    }
+    ranges
+}
+
+/// The value closest to the level at `index` making both of its steps safe
+fn closest_fit(
+    nums: &[Level],
+    index: usize,
+    policy: &SafetyPolicy,
+    increasing: bool,
+) -> Option<Level> {
+    let mut ranges = vec![(Level::MIN as i128, Level::MAX as i128)];
+    if index > 0 {
+        ranges = intersect(&ranges, &fits_after(nums[index - 1], policy, increasing));
+    }
+    if index + 1 < nums.len() {
+        ranges = intersect(&ranges, &fits_before(nums[index + 1], policy, increasing));
+    }
+
+    // Every value of a range makes the same steps safe, so the closest one is enough
+    let level = nums[index] as i128;
+    ranges
+        .into_iter()
+        .map(|(lo, hi)| level.clamp(lo, hi))
+        .min_by_key(|x| x.abs_diff(level))
+        .map(|x| x as Level)
+}
+
+/// The smallest single edit that makes an unsafe report safe, `None` for a report
+/// that is already safe or that no single edit can save.
+///
+/// A substitution keeps every reading, so it comes first: the one changing a level
+/// by the smallest amount, the earliest level on a tie. When no substitution
+/// works, for instance `1 3 2 4 5` where no level fits between `1` and `2`,
+/// the level removed by the Problem Dampener is dropped instead.
+pub fn repair(nums: &[Level], policy: &SafetyPolicy) -> Option<Repair> {
+    let mut best: Option<(u64, usize, Level)> = None;
+
+    for &increasing in policy.direction.candidates() {
+        let v = first_violation(nums, policy, increasing, None)?;
+        // Like with the dampener, the steps before the first bad one are unchanged
+        // by any substitution further on, so it has to be one of its two levels
+        for index in [v.index, v.next] {
+            let Some(to) = closest_fit(nums, index, policy, increasing) else {
+                continue;
+            };
+            let mut levels = nums.to_vec();
+            levels[index] = to;
+            if first_violation(&levels, policy, increasing, None).is_some() {
+                continue;
+            }
+            let candidate = (nums[index].abs_diff(to), index, to);
+            if best.is_none_or(|b| (candidate.0, candidate.1) < (b.0, b.1)) {
+                best = Some(candidate);
+            }
+        }
+    }
+
+    if let Some((_, index, to)) = best {
+        let mut levels = nums.to_vec();
+        levels[index] = to;
+        return Some(Repair {
+            edit: Edit::Substitute {
+                index,
+                from: nums[index],
+                to,
+            },
+            levels,
+        });
+    }
+
+    let index = dampen(nums, policy)??;
+    let mut levels = nums.to_vec();
+    let level = levels.remove(index);
+    Some(Repair {
+        edit: Edit::Remove { index, level },
+        levels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::is_line_safe;
+    use crate::policy::Direction;
+
+    fn levels(report: &str) -> Vec<Level> {
+        report
+            .split_whitespace()
+            .map(|l| l.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn repairs_the_example() {
+        let policy = SafetyPolicy::default();
+        let repaired: Vec<Option<(Vec<Level>, String)>> = [
+            "7 6 4 2 1",
+            "1 2 7 8 9",
+            "9 7 6 2 1",
+            "1 3 2 4 5",
+            "8 6 4 4 1",
+            "1 3 6 7 9",
+        ]
+        .iter()
+        .map(|report| repair(&levels(report), &policy).map(|r| (r.levels, r.edit.to_string())))
+        .collect();
+
+        assert_eq!(
+            vec![
+                None,
+                Some((levels("1 4 7 8 9"), "replace index 1: 2 -> 4".to_string())),
+                Some((levels("9 7 5 2 1"), "replace index 2: 6 -> 5".to_string())),
+                Some((levels("1 2 4 5"), "remove index 1 (3)".to_string())),
+                Some((levels("8 6 5 4 1"), "replace index 2: 4 -> 5".to_string())),
+                None,
+            ],
+            repaired
+        );
+        assert_eq!(None, repair(&[1, 9, 2, 8, 3, 7], &policy));
+    }
+
+    #[test]
+    fn finds_the_smallest_substitution() {
+        let mut next = crate::tests::xorshift(36);
+        let policies = [
+            SafetyPolicy::default(),
+            SafetyPolicy {
+                allow_flat: true,
+                min_step: 2,
+                ..SafetyPolicy::default()
+            },
+            SafetyPolicy {
+                max_step: 5,
+                direction: Direction::Decreasing,
+                ..SafetyPolicy::default()
+            },
+        ];
+
+        for _ in 0..5000 {
+            let len = 1 + next(7);
+            let mut level: Level = 0;
+            let mut nums: Vec<Level> = Vec::new();
+            for _ in 0..len {
+                nums.push(level);
+                level += next(11) as Level - 5;
+            }
+
+            for policy in &policies {
+                // Every value a level could be replaced with lies within reach of the others
+                let mut smallest: Option<u64> = None;
+                if !is_line_safe(&nums, policy) {
+                    for i in 0..nums.len() {
+                        for to in -60..60 {
+                            let mut edited = nums.clone();
+                            edited[i] = to;
+                            if is_line_safe(&edited, policy) {
+                                let change = nums[i].abs_diff(to);
+                                smallest = Some(smallest.map_or(change, |s| s.min(change)));
+                            }
+                        }
+                    }
+                }
+
+                let repaired = repair(&nums, policy);
+                if let Some(r) = &repaired {
+                    assert!(is_line_safe(&r.levels, policy), "{nums:?} {r:?}");
+                }
+                match repaired.map(|r| r.edit) {
+                    Some(Edit::Substitute { from, to, .. }) => {
+                        assert_eq!(smallest, Some(from.abs_diff(to)), "{nums:?} {policy:?}")
+                    }
+                    Some(Edit::Remove { .. }) => assert_eq!(None, smallest, "{nums:?} {policy:?}"),
+                    None => assert_eq!(None, smallest, "{nums:?} {policy:?}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stays_within_the_range_of_levels() {
+        let policy = SafetyPolicy::default();
+        let r = repair(&[Level::MIN, Level::MIN + 9, Level::MIN + 2], &policy).unwrap();
+        assert_eq!(vec![Level::MIN, Level::MIN + 1, Level::MIN + 2], r.levels);
+
+        // Nothing fits above the largest level, only the dampener helps
+        let r = repair(&[Level::MAX - 1, Level::MAX, Level::MAX], &policy).unwrap();
+        assert_eq!(vec![Level::MAX - 1, Level::MAX], r.levels);
+        assert_eq!(None, repair(&[Level::MAX; 3], &policy));
+    }
+}
@@ -0,0 +1,172 @@
+use crate::parse::Level;
+use crate::policy::{SafetyPolicy, ViolationKind};
+
+/// A step between two levels of a report breaking the policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Violation {
+    /// Index of the first level of the step
+    pub index: usize,
+    /// Index of the second level, the one right after `index`
+    /// unless a level in between was skipped
+    pub next: usize,
+    pub values: (Level, Level),
+    pub kind: ViolationKind,
+}
+
+/// Finds the first step breaking the policy in the given direction, ignoring
+/// the level at index `skip` if any. Every check of a report goes through here.
+pub fn first_violation(
+    nums: &[Level],
+    policy: &SafetyPolicy,
+    increasing: bool,
+    skip: Option<usize>,
+) -> Option<Violation> {
+    let mut levels = nums.iter().enumerate().filter(|(i, _)| Some(*i) != skip);
+    let (mut index, mut a) = levels.next()?;
+
+    for (next, b) in levels {
+        if let Err(kind) = policy.check_step(*a, *b, increasing) {
+            return Some(Violation {
+                index,
+                next,
+                values: (*a, *b),
+                kind,
+            });
+        }
+        (index, a) = (next, b);
+    }
+    None
+}
+
+/// What the Problem Dampener does with a report: `Some(None)` if the report is
+/// safe as it is, `Some(Some(i))` if it is safe once the level `i` is removed,
+/// `None` if it is unsafe either way.
+pub fn dampen(nums: &[Level], policy: &SafetyPolicy) -> Option<Option<usize>> {
+    let candidates = policy.direction.candidates();
+    let violations: Vec<(bool, Option<Violation>)> = candidates
+        .iter()
+        .map(|&increasing| (increasing, first_violation(nums, policy, increasing, None)))
+        .collect();
+    if violations.iter().any(|(_, v)| v.is_none()) {
+        return Some(None);
+    }
+
+    // Everything before the first bad pair is fine, so removing any of those levels
+    // leaves the bad pair in place. Only removing one of the two levels of the pair
+    // can help, which makes it at most three scans of the report per direction.
+    for (increasing, violation) in violations {
+        let v = violation.unwrap();
+        for skip in [v.index, v.next] {
+            if first_violation(nums, policy, increasing, Some(skip)).is_none() {
+                return Some(Some(skip));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::is_line_safe;
+    use crate::policy::Direction;
+
+    /// The check as it was first written for the puzzle, one direction after the other
+    fn is_line_safe_mirrored(nums: &[u8]) -> bool {
+        let dec = nums
+            .iter()
+            .zip(nums.iter().skip(1))
+            .all(|(a, b)| b < a && a <= &(b + 3));
+        let inc = nums
+            .iter()
+            .zip(nums.iter().skip(1))
+            .all(|(a, b)| a < b && &(a + 3) >= b);
+        dec || inc
+    }
+
+    #[test]
+    fn matches_the_mirrored_check() {
+        let mut next = crate::tests::xorshift(34);
+        for _ in 0..20000 {
+            let len = next(8);
+            let mut level = next(20) as i32;
+            let mut nums: Vec<u8> = Vec::new();
+            for _ in 0..len {
+                nums.push(level as u8);
+                level = (level + next(9) as i32 - 4).max(0);
+            }
+
+            let levels: Vec<Level> = nums.iter().map(|&n| n as Level).collect();
+            assert_eq!(
+                is_line_safe_mirrored(&nums),
+                is_line_safe(&levels, &SafetyPolicy::default()),
+                "{nums:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn both_directions_follow_the_same_rules() {
+        let mut next = crate::tests::xorshift(340);
+        for _ in 0..20000 {
+            let policy = SafetyPolicy {
+                min_step: next(3) as u64,
+                max_step: next(6) as u64,
+                allow_flat: next(2) == 0,
+                direction: Direction::Either,
+            };
+            let a = next(21) as Level - 10;
+            let b = next(21) as Level - 10;
+
+            // Going up from `a` to `b` is the same as going down from `-a` to `-b`
+            assert_eq!(
+                policy.check_step(a, b, true),
+                policy.check_step(-a, -b, false)
+            );
+
+            let step = a.abs_diff(b);
+            let expected = if a == b {
+                policy.allow_flat
+            } else {
+                a < b && policy.min_step <= step && step <= policy.max_step
+            };
+            assert_eq!(
+                expected,
+                policy.is_step_safe(a, b, true),
+                "{a} {b} {policy:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn handles_the_widest_steps() {
+        let policy = SafetyPolicy {
+            max_step: u64::MAX,
+            ..SafetyPolicy::default()
+        };
+        assert!(policy.is_step_safe(Level::MIN, Level::MAX, true));
+        assert_eq!(
+            Err(ViolationKind::DirectionChange),
+            policy.check_step(Level::MAX, Level::MIN, true)
+        );
+        assert_eq!(
+            Err(ViolationKind::StepTooLarge),
+            SafetyPolicy::default().check_step(Level::MIN, Level::MAX, true)
+        );
+    }
+
+    #[test]
+    fn finds_violations_around_a_skipped_level() {
+        let nums = [1, 3, 2, 4, 9];
+        let policy = SafetyPolicy::default();
+        let v = first_violation(&nums, &policy, true, Some(2)).unwrap();
+        assert_eq!((3, 4, (4, 9)), (v.index, v.next, v.values));
+
+        let v = first_violation(&nums, &policy, true, Some(1)).unwrap();
+        assert_eq!(
+            (3, 4, ViolationKind::StepTooLarge),
+            (v.index, v.next, v.kind)
+        );
+        assert_eq!(Some(Some(1)), dampen(&[1, 3, 2, 4], &policy));
+    }
+}
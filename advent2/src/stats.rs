@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::explain::find_violation;
+use crate::parse::{parse_report, Level, ParseError};
+use crate::policy::{SafetyPolicy, ViolationKind};
+use crate::{is_line_safe, is_line_safe_with_dampener};
+
+/// An overview of all the reports of an input
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub reports: u32,
+    /// Number of reports of each length
+    pub lengths: BTreeMap<usize, u32>,
+    /// Reports going up or down, by their first step that is not flat
+    pub increasing: u32,
+    pub decreasing: u32,
+    /// Reports without any step that is not flat, empty ones included
+    pub flat: u32,
+    /// Number of steps of each size, in both directions
+    pub steps: BTreeMap<u64, u32>,
+    pub safe: u32,
+    /// Unsafe reports the Problem Dampener makes safe
+    pub fixed_by_dampener: u32,
+    pub irreparable: u32,
+    /// Number of unsafe reports by the kind of their first violation,
+    /// in the order the kinds first appear
+    pub violations: Vec<(ViolationKind, u32)>,
+}
+
+impl Summary {
+    /// The kind of violation making the most reports unsafe, the first to
+    /// appear on a tie, with its count
+    pub fn most_common_violation(&self) -> Option<(ViolationKind, u32)> {
+        self.violations
+            .iter()
+            .copied()
+            .reduce(|best, v| if v.1 > best.1 { v } else { best })
+    }
+}
+
+pub fn summarize(input_levels: &str, policy: &SafetyPolicy) -> Result<Summary, ParseError> {
+    let mut summary = Summary::default();
+
+    for (i, line) in input_levels.lines().enumerate() {
+        let nums = parse_report(i + 1, line)?;
+        summary.reports += 1;
+        *summary.lengths.entry(nums.len()).or_insert(0) += 1;
+
+        let steps: Vec<(Level, Level)> = nums
+            .iter()
+            .copied()
+            .zip(nums.iter().copied().skip(1))
+            .collect();
+        match steps.iter().find(|(a, b)| a != b) {
+            Some((a, b)) if a < b => summary.increasing += 1,
+            Some(_) => summary.decreasing += 1,
+            None => summary.flat += 1,
+        }
+        for (a, b) in steps {
+            *summary.steps.entry(a.abs_diff(b)).or_insert(0) += 1;
+        }
+
+        if is_line_safe(&nums, policy) {
+            summary.safe += 1;
+            continue;
+        }
+        if is_line_safe_with_dampener(&nums, policy) {
+            summary.fixed_by_dampener += 1;
+        } else {
+            summary.irreparable += 1;
+        }
+        let kind = find_violation(&nums, policy).unwrap().kind;
+        match summary.violations.iter_mut().find(|(k, _)| *k == kind) {
+            Some((_, count)) => *count += 1,
+            None => summary.violations.push((kind, 1)),
+        }
+    }
+    Ok(summary)
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |n: u32| 100.0 * n as f64 / self.reports.max(1) as f64;
+        let join = |counts: Vec<String>| counts.join(", ");
+
+        writeln!(f, "reports: {}", self.reports)?;
+        writeln!(
+            f,
+            "lengths: {}",
+            join(
+                self.lengths
+                    .iter()
+                    .map(|(l, n)| format!("{l}: {n}"))
+                    .collect()
+            )
+        )?;
+        writeln!(
+            f,
+            "directions: {} increasing ({:.1}%), {} decreasing ({:.1}%), {} flat ({:.1}%)",
+            self.increasing,
+            percent(self.increasing),
+            self.decreasing,
+            percent(self.decreasing),
+            self.flat,
+            percent(self.flat)
+        )?;
+        writeln!(
+            f,
+            "steps: {}",
+            join(
+                self.steps
+                    .iter()
+                    .map(|(s, n)| format!("{s}: {n}"))
+                    .collect()
+            )
+        )?;
+        writeln!(
+            f,
+            "safe: {}, fixed by dampener: {}, irreparable: {}",
+            self.safe, self.fixed_by_dampener, self.irreparable
+        )?;
+        writeln!(
+            f,
+            "violations: {}",
+            join(
+                self.violations
+                    .iter()
+                    .map(|(k, n)| format!("{k}: {n}"))
+                    .collect()
+            )
+        )?;
+        match self.most_common_violation() {
+            Some((kind, count)) => write!(f, "most common violation: {kind} ({count} reports)"),
+            None => write!(f, "most common violation: none"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn summarizes_the_example() {
+        let input: String =
+            fs::read_to_string("src/test_input.txt").expect("Could not read the puzzle input");
+        let summary = summarize(&input, &SafetyPolicy::default()).unwrap();
+
+        assert_eq!(
+            "reports: 6\n\
+             lengths: 5: 6\n\
+             directions: 3 increasing (50.0%), 3 decreasing (50.0%), 0 flat (0.0%)\n\
+             steps: 0: 1, 1: 10, 2: 9, 3: 2, 4: 1, 5: 1\n\
+             safe: 2, fixed by dampener: 2, irreparable: 2\n\
+             violations: step too large: 2, direction change: 1, flat: 1\n\
+             most common violation: step too large (2 reports)",
+            summary.to_string()
+        );
+    }
+
+    #[test]
+    fn adds_up() {
+        let input = "1 1 1\n\n5\n3 3 2 1\n1 2 3 4 5 6 7\n7 7 7 7\n";
+        let summary = summarize(input, &SafetyPolicy::default()).unwrap();
+
+        assert_eq!(6, summary.reports);
+        assert_eq!(
+            summary.reports,
+            summary.increasing + summary.decreasing + summary.flat
+        );
+        assert_eq!(
+            (1, 1, 4),
+            (summary.increasing, summary.decreasing, summary.flat)
+        );
+        assert_eq!(
+            summary.reports,
+            summary.safe + summary.fixed_by_dampener + summary.irreparable
+        );
+        assert_eq!(
+            (3, 1, 2),
+            (summary.safe, summary.fixed_by_dampener, summary.irreparable)
+        );
+        assert_eq!(
+            Some((ViolationKind::Flat, 3)),
+            summary.most_common_violation()
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use std::fmt;
+
+use crate::instruction::{Instruction, InstructionSet, Token, Tokenizer};
+use crate::interpreter::{Interpreter, Overflow};
+
+/// Why a piece of memory looking like an instruction is not one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// Brackets other than parentheses, e.g. `mul[3,7]` or `mul(32,64]`
+    BadDelimiter,
+    /// Spaces around the name or the arguments, e.g. `mul ( 2 , 4 )`
+    Whitespace,
+    /// An argument with more digits than allowed, e.g. `mul(1234,5)`
+    TooManyDigits,
+    /// The arguments are never closed, e.g. `mul(4*`
+    MissingParen,
+    /// The wrong number of arguments, or empty ones, e.g. `mul(1,2,3)` or `mul(,)`
+    BadArguments,
+}
+
+impl Reason {
+    pub const ALL: [Reason; 5] = [
+        Reason::BadDelimiter,
+        Reason::Whitespace,
+        Reason::TooManyDigits,
+        Reason::MissingParen,
+        Reason::BadArguments,
+    ];
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Reason::BadDelimiter => "bad delimiter",
+            Reason::Whitespace => "whitespace",
+            Reason::TooManyDigits => "too many digits",
+            Reason::MissingParen => "missing paren",
+            Reason::BadArguments => "bad arguments",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A piece of memory that looks like an instruction without being one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NearMiss {
+    pub offset: usize,
+    pub len: usize,
+    /// The name of the instruction it looks like
+    pub name: &'static str,
+    pub reason: Reason,
+}
+
+impl NearMiss {
+    /// The instruction read leniently, ignoring the spaces and the number of digits
+    /// of the arguments, as long as they fit in a `u32`
+    pub fn lenient(&self, memory: &[u8], set: &InstructionSet) -> Option<Instruction> {
+        if !matches!(self.reason, Reason::Whitespace | Reason::TooManyDigits) {
+            return None;
+        }
+        let text: String = String::from_utf8_lossy(&memory[self.offset..self.offset + self.len])
+            .chars()
+            .filter(|c| *c != ' ')
+            .collect();
+        let args = text
+            .strip_prefix(self.name)?
+            .strip_prefix('(')?
+            .strip_suffix(')')?;
+
+        let word = set
+            .words()
+            .iter()
+            .find(|(word, _)| word.strip_suffix("()") == Some(self.name));
+        match word {
+            Some((_, instruction)) if args.is_empty() => Some(instruction.clone()),
+            Some(_) => None,
+            None => {
+                let op = set.find(self.name)?;
+                let args: Vec<u32> = args
+                    .split(',')
+                    .map(|a| a.parse().ok())
+                    .collect::<Option<_>>()?;
+                set.operation(op)
+                    .arity
+                    .contains(&args.len())
+                    .then_some(Instruction::Op { op, args })
+            }
+        }
+    }
+}
+
+fn classify(text: &[u8], name: &str, set: &InstructionSet) -> Reason {
+    let rest = &text[name.len()..];
+    let closed = rest.last().is_some_and(|b| b")]}>".contains(b));
+    // `do`, `don't` and `toggle` take no arguments, so they never have too many digits
+    let op = set.find(name).map(|op| set.operation(op));
+
+    if rest.contains(&b' ') {
+        Reason::Whitespace
+    } else if rest[0] != b'(' || (closed && rest.last() != Some(&b')')) {
+        Reason::BadDelimiter
+    } else if op.is_some_and(|op| {
+        rest.split(|b| !b.is_ascii_digit())
+            .any(|digits| digits.len() > *op.digits.end())
+    }) {
+        Reason::TooManyDigits
+    } else if !closed {
+        Reason::MissingParen
+    } else {
+        Reason::BadArguments
+    }
+}
+
+/// The pieces of memory that look like an instruction without being one: the name
+/// of an instruction that does not start one, followed by an opening bracket,
+/// up to the closing bracket or to the first character that has nothing to do
+/// in the arguments, e.g. `mul[3,7]`, `mul(32,64]` or `mul(4`.
+pub fn near_misses(memory: &[u8], set: &InstructionSet, tokens: &[Token]) -> Vec<NearMiss> {
+    let names = set.names();
+    let mut misses: Vec<NearMiss> = Vec::new();
+    let mut tokens = tokens.iter().peekable();
+    let mut i = 0;
+
+    while i < memory.len() {
+        // Nothing inside a real instruction is a near miss
+        if let Some(token) = tokens.next_if(|t| t.offset <= i) {
+            i = i.max(token.offset + token.len);
+            continue;
+        }
+        let Some(&name) = names.iter().find(|n| memory[i..].starts_with(n.as_bytes())) else {
+            i += 1;
+            continue;
+        };
+
+        let mut end = i + name.len();
+        while memory.get(end) == Some(&b' ') {
+            end += 1;
+        }
+        if !memory.get(end).is_some_and(|b| b"([{<".contains(b)) {
+            i += name.len();
+            continue;
+        }
+        end += 1;
+        while let Some(&b) = memory.get(end) {
+            if tokens.peek().is_some_and(|t| t.offset == end) {
+                break;
+            }
+            if b")]}>".contains(&b) {
+                end += 1;
+                break;
+            }
+            if !(b.is_ascii_digit() || b == b',' || b == b' ') {
+                break;
+            }
+            end += 1;
+        }
+        // Spaces after arguments that are never closed are not part of them
+        if !memory[end - 1..end].iter().any(|b| b")]}>".contains(b)) {
+            while memory[end - 1] == b' ' {
+                end -= 1;
+            }
+        }
+        misses.push(NearMiss {
+            offset: i,
+            len: end - i,
+            name,
+            reason: classify(&memory[i..end], name, set),
+        });
+        i = end;
+    }
+    misses
+}
+
+/// Runs the instructions of the memory along with the near misses that can be
+/// read leniently, see [`NearMiss::lenient`]
+pub fn run_lenient(memory: &[u8], set: &InstructionSet) -> Result<Interpreter, Overflow> {
+    let tokens: Vec<Token> = Tokenizer::new(memory, set).collect();
+    let mut instructions: Vec<(usize, Instruction)> = near_misses(memory, set, &tokens)
+        .iter()
+        .filter_map(|miss| Some((miss.offset, miss.lenient(memory, set)?)))
+        .collect();
+    instructions.extend(tokens.into_iter().map(|t| (t.offset, t.instruction)));
+    instructions.sort_by_key(|(offset, _)| *offset);
+
+    let mut interpreter = Interpreter::new(set);
+    for (_, instruction) in &instructions {
+        interpreter.execute(set, instruction)?;
+    }
+    Ok(interpreter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::Operation;
+    use crate::interpreter::run;
+
+    fn diagnose(memory: &[u8], set: &InstructionSet) -> Vec<(String, Reason)> {
+        let tokens: Vec<Token> = Tokenizer::new(memory, set).collect();
+        near_misses(memory, set, &tokens)
+            .into_iter()
+            .map(|miss| {
+                let text = &memory[miss.offset..miss.offset + miss.len];
+                (String::from_utf8_lossy(text).to_string(), miss.reason)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn classifies_near_misses() {
+        let memory = b"mul(4* mul(6,9  ! ?(12,34) mul ( 2 , 4 ) mul(1234,5)do(mul(1,2)\
+                       mul[3,7]mul(32,64]mul(1,2,3)mul(,)don't(1)";
+        assert_eq!(
+            [
+                ("mul(4", Reason::MissingParen),
+                ("mul(6,9", Reason::MissingParen),
+                ("mul ( 2 , 4 )", Reason::Whitespace),
+                ("mul(1234,5)", Reason::TooManyDigits),
+                ("do(", Reason::MissingParen),
+                ("mul[3,7]", Reason::BadDelimiter),
+                ("mul(32,64]", Reason::BadDelimiter),
+                ("mul(1,2,3)", Reason::BadArguments),
+                ("mul(,)", Reason::BadArguments),
+                ("don't(1)", Reason::BadArguments),
+            ]
+            .map(|(text, reason)| (text.to_string(), reason))
+            .to_vec(),
+            diagnose(memory, &InstructionSet::default())
+        );
+    }
+
+    #[test]
+    fn lenient_mode_accepts_spaces_and_long_numbers() {
+        let mut set = InstructionSet::default();
+        set.register(Operation::builtin("max").unwrap());
+        let memory = b"mul(2,3) mul ( 2 , 4 )don't ()mul(1000,1)do( )max (1, 5,2)mul[3,7]mul(3,4";
+
+        let strict = run(std::str::from_utf8(memory).unwrap(), &set).unwrap();
+        assert_eq!((6, 6), (strict.total, strict.enabled_total));
+        let lenient = run_lenient(memory, &set).unwrap();
+        assert_eq!(
+            (6 + 8 + 1000 + 5, 6 + 8 + 5),
+            (lenient.total, lenient.enabled_total)
+        );
+    }
+
+    #[test]
+    fn lenient_mode_can_disable_more() {
+        let set = InstructionSet::default();
+        let memory = b"mul(2,3)don't ()mul(5,5)";
+
+        let strict = run(std::str::from_utf8(memory).unwrap(), &set).unwrap();
+        assert_eq!((31, 31), (strict.total, strict.enabled_total));
+        let lenient = run_lenient(memory, &set).unwrap();
+        assert_eq!((31, 6), (lenient.total, lenient.enabled_total));
+    }
+}
@@ -0,0 +1,153 @@
+use crate::diagnostics::near_misses;
+use crate::instruction::{Instruction, InstructionSet, Token, Tokenizer};
+use crate::interpreter::Interpreter;
+
+const RESET: &str = "\x1b[0m";
+
+/// How a byte of the memory is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    Plain,
+    /// Where the operations are disabled, e.g. between a `don't()` and the next `do()`
+    Disabled,
+    Operation,
+    DisabledOperation,
+    Conditional,
+    NearMiss,
+}
+
+impl Style {
+    fn code(&self) -> &'static str {
+        match self {
+            Style::Plain => RESET,
+            Style::Disabled => "\x1b[2m",
+            Style::Operation => "\x1b[1;32m",
+            Style::DisabledOperation => "\x1b[2;32m",
+            Style::Conditional => "\x1b[1;36m",
+            Style::NearMiss => "\x1b[4;31m",
+        }
+    }
+}
+
+/// The memory with the instructions highlighted, the disabled regions dimmed
+/// and the near misses underlined, using ANSI colors
+pub fn annotate(memory: &[u8], set: &InstructionSet) -> String {
+    let tokens: Vec<Token> = Tokenizer::new(memory, set).collect();
+    let mut styles = vec![Style::Plain; memory.len()];
+
+    // Only follows the conditionals, the operations are not run
+    let mut state = Interpreter::new(set);
+    let mut last = 0;
+    for token in &tokens {
+        if !state.enabled {
+            styles[last..token.offset].fill(Style::Disabled);
+        }
+        let style = match token.instruction {
+            Instruction::Op { .. } if state.enabled => Style::Operation,
+            Instruction::Op { .. } => Style::DisabledOperation,
+            _ => Style::Conditional,
+        };
+        styles[token.offset..token.offset + token.len].fill(style);
+        state.apply_conditional(set, &token.instruction);
+        last = token.offset + token.len;
+    }
+    if !state.enabled {
+        styles[last..].fill(Style::Disabled);
+    }
+    for miss in near_misses(memory, set, &tokens) {
+        styles[miss.offset..miss.offset + miss.len].fill(Style::NearMiss);
+    }
+
+    let mut annotated = String::new();
+    let mut start = 0;
+    for i in 1..=memory.len() {
+        if i == memory.len() || styles[i] != styles[start] {
+            if styles[start] != Style::Plain {
+                annotated.push_str(styles[start].code());
+            }
+            annotated.push_str(&String::from_utf8_lossy(&memory[start..i]));
+            if styles[start] != Style::Plain {
+                annotated.push_str(RESET);
+            }
+            start = i;
+        }
+    }
+    annotated
+}
+
+/// One line per instruction, with its offset, its text and the value it adds
+/// to the result, and one per near miss with the reason it is not an instruction
+pub fn listing(memory: &[u8], set: &InstructionSet) -> Vec<String> {
+    let tokens: Vec<Token> = Tokenizer::new(memory, set).collect();
+    let text = |offset: usize, len: usize| String::from_utf8_lossy(&memory[offset..offset + len]);
+
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    let mut state = Interpreter::new(set);
+    for token in &tokens {
+        let line = match &token.instruction {
+            Instruction::Op { op, args } => {
+                let value = match (set.operation(*op).eval)(args) {
+                    Some(value) => value.to_string(),
+                    None => "overflow".to_string(),
+                };
+                let status = if state.enabled { "" } else { " (disabled)" };
+                format!("{:<16} {value:>8}{status}", text(token.offset, token.len))
+            }
+            _ => text(token.offset, token.len).to_string(),
+        };
+        state.apply_conditional(set, &token.instruction);
+        lines.push((token.offset, line));
+    }
+    for miss in near_misses(memory, set, &tokens) {
+        let line = format!(
+            "{:<16} near miss, {}",
+            text(miss.offset, miss.len),
+            miss.reason
+        );
+        lines.push((miss.offset, line));
+    }
+
+    lines.sort_by_key(|(offset, _)| *offset);
+    lines
+        .into_iter()
+        .map(|(offset, line)| format!("{offset:>8}  {line}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &[u8] =
+        b"xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+
+    #[test]
+    fn highlights_the_example() {
+        let annotated = annotate(EXAMPLE, &InstructionSet::default());
+        assert_eq!(
+            "x\x1b[1;32mmul(2,4)\x1b[0m&\x1b[4;31mmul[3,7]\x1b[0m!^\
+             \x1b[1;36mdon't()\x1b[0m\x1b[2m_\x1b[0m\x1b[2;32mmul(5,5)\x1b[0m\
+             \x1b[2m+\x1b[0m\x1b[4;31mmul(32,64]\x1b[0m\x1b[2m(\x1b[0m\
+             \x1b[2;32mmul(11,8)\x1b[0m\x1b[2mun\x1b[0m\x1b[1;36mdo()\x1b[0m?\
+             \x1b[1;32mmul(8,5)\x1b[0m)",
+            annotated
+        );
+    }
+
+    #[test]
+    fn lists_the_example() {
+        assert_eq!(
+            vec![
+                "       1  mul(2,4)                8",
+                "      10  mul[3,7]         near miss, bad delimiter",
+                "      20  don't()",
+                "      28  mul(5,5)               25 (disabled)",
+                "      37  mul(32,64]       near miss, bad delimiter",
+                "      48  mul(11,8)              88 (disabled)",
+                "      59  do()",
+                "      64  mul(8,5)               40",
+            ],
+            listing(EXAMPLE, &InstructionSet::default())
+        );
+    }
+}
@@ -0,0 +1,534 @@
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use regex::bytes::Regex;
+
+use crate::machine::Machine;
+
+/// An operation the program may run, with the exact syntax it is written with:
+/// `name(X,Y,...)` with no space, between `arity` comma separated arguments,
+/// each of them a number with `digits` digits
+#[derive(Debug, Clone)]
+pub struct Operation {
+    pub name: &'static str,
+    pub arity: RangeInclusive<usize>,
+    pub digits: RangeInclusive<usize>,
+    /// What the operation adds to the result of the program,
+    /// `None` when it does not fit in a `u64`
+    pub eval: fn(&[u32]) -> Option<u64>,
+}
+
+impl Operation {
+    /// The most arguments an operation may have other than any number of them,
+    /// the bounded repetitions of the pattern being expanded when it is compiled
+    pub const MAX_ARITY: usize = 1000;
+
+    /// The operation with the rules of `mul`: two arguments of 1-3 digits
+    pub fn new(name: &'static str, eval: fn(&[u32]) -> Option<u64>) -> Self {
+        Operation {
+            name,
+            arity: 2..=2,
+            digits: 1..=3,
+            eval,
+        }
+    }
+
+    /// The operations known besides `mul`, by name
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "mul" => Some(Operation::new("mul", |args| {
+                args.iter()
+                    .try_fold(1u64, |product, &a| product.checked_mul(a as u64))
+            })),
+            "add" => Some(Operation::new("add", |args| {
+                args.iter()
+                    .try_fold(0u64, |sum, &a| sum.checked_add(a as u64))
+            })),
+            // The first argument minus the others, never going below 0
+            "sub" => Some(Operation::new("sub", |args| {
+                let (first, rest) = args.split_first()?;
+                Some(rest.iter().fold(*first, |a, &b| a.saturating_sub(b)) as u64)
+            })),
+            // The first argument divided by the others, dividing by 0 giving nothing
+            "div" => Some(Operation::new("div", |args| {
+                let (first, rest) = args.split_first()?;
+                Some(
+                    rest.iter()
+                        .fold(*first, |a, &b| a.checked_div(b).unwrap_or(0))
+                        as u64,
+                )
+            })),
+            "max" => Some(Operation {
+                arity: 1..=usize::MAX,
+                ..Operation::new("max", |args| args.iter().max().map(|&m| m as u64))
+            }),
+            _ => None,
+        }
+    }
+
+    /// Whether `s` is the beginning of this operation, cut before its closing parenthesis
+    fn is_cut(&self, s: &[u8]) -> bool {
+        let name = self.name.as_bytes();
+        if s.len() <= name.len() {
+            return name.starts_with(s);
+        }
+        if !s.starts_with(name) || s[name.len()] != b'(' {
+            return false;
+        }
+
+        // The last argument may still be missing some digits, or all of them
+        let args: Vec<&[u8]> = s[name.len() + 1..].split(|&b| b == b',').collect();
+        let (last, complete) = args.split_last().unwrap();
+        args.len() <= *self.arity.end()
+            && args.iter().flat_map(|a| a.iter()).all(u8::is_ascii_digit)
+            && complete.iter().all(|a| self.digits.contains(&a.len()))
+            && last.len() <= *self.digits.end()
+    }
+
+    /// The pattern of the operation, its arguments in the group `op{index}`
+    fn pattern(&self, index: usize) -> String {
+        // Only ASCII digits, `\d` would take any Unicode digit
+        let number = format!(r"[0-9]{{{},{}}}", self.digits.start(), self.digits.end());
+        let more = match *self.arity.end() {
+            usize::MAX => format!("{{{},}}", self.arity.start() - 1),
+            end => format!("{{{},{}}}", self.arity.start() - 1, end - 1),
+        };
+        format!(
+            r"{}\((?<op{index}>{number}(?:,{number}){more})\)",
+            regex::escape(self.name)
+        )
+    }
+}
+
+/// How the tokenizer finds the instructions in the memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Matcher {
+    Regex,
+    /// The hand-written [`Machine`], reading one byte at a time
+    StateMachine,
+}
+
+impl FromStr for Matcher {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "regex" => Ok(Matcher::Regex),
+            "machine" => Ok(Matcher::StateMachine),
+            _ => Err(format!("Unknown matcher: {s}")),
+        }
+    }
+}
+
+/// What `do()` and `don't()` mean to the operations that follow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conditionals {
+    /// `do()` enables them, `don't()` disables them, only the most recent one applies
+    Switch,
+    /// Each `don't()` must be matched by a `do()` before they are enabled again
+    Nested,
+    /// As [`Conditionals::Switch`], with `toggle()` switching them the other way
+    Toggle,
+}
+
+impl FromStr for Conditionals {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "switch" => Ok(Conditionals::Switch),
+            "nested" => Ok(Conditionals::Nested),
+            "toggle" => Ok(Conditionals::Toggle),
+            _ => Err(format!("Unknown conditionals: {s}")),
+        }
+    }
+}
+
+/// The instructions the tokenizer recognizes: the operations, `do()` and `don't()`,
+/// and the rules the interpreter follows for them
+#[derive(Debug, Clone)]
+pub struct InstructionSet {
+    operations: Vec<Operation>,
+    conditionals: Conditionals,
+    /// The instructions written as a fixed word, built again on each change
+    words: Vec<(&'static str, Instruction)>,
+    /// The names of the groups of the arguments of the operations, by index
+    groups: Vec<String>,
+    /// A single pattern for every instruction, built again on each change
+    regex: Regex,
+    pub matcher: Matcher,
+    /// Whether the operations are enabled at the beginning of the program
+    pub start_enabled: bool,
+}
+
+impl Default for InstructionSet {
+    /// Only `mul`, as in the puzzle
+    fn default() -> Self {
+        let mut set = InstructionSet {
+            operations: vec![Operation::builtin("mul").unwrap()],
+            conditionals: Conditionals::Switch,
+            words: Vec::new(),
+            groups: Vec::new(),
+            regex: Regex::new("").unwrap(),
+            matcher: Matcher::Regex,
+            start_enabled: true,
+        };
+        set.rebuild();
+        set
+    }
+}
+
+impl InstructionSet {
+    /// Adds an operation, replacing any operation of the same name
+    pub fn register(&mut self, operation: Operation) {
+        // The state machine starts matching a name on its first byte
+        assert!(!operation.name.is_empty(), "Operations have a name");
+        // Arguments are read as `u32`, which holds any number of 9 digits
+        assert!(*operation.arity.start() >= 1, "Operations take arguments");
+        let end = *operation.arity.end();
+        assert!(
+            end == usize::MAX || end <= Operation::MAX_ARITY,
+            "Operations take at most {} arguments, or any number of them",
+            Operation::MAX_ARITY
+        );
+        assert!(*operation.digits.start() >= 1 && *operation.digits.end() <= 9);
+        self.operations.retain(|op| op.name != operation.name);
+        self.operations.push(operation);
+        self.rebuild();
+    }
+
+    pub fn conditionals(&self) -> Conditionals {
+        self.conditionals
+    }
+
+    /// Changes the rules of the conditionals, `toggle()` only being recognized
+    /// with [`Conditionals::Toggle`]
+    pub fn set_conditionals(&mut self, conditionals: Conditionals) {
+        self.conditionals = conditionals;
+        self.rebuild();
+    }
+
+    /// The instructions written as a fixed word, `don't()` before `do()`
+    pub fn words(&self) -> &[(&'static str, Instruction)] {
+        &self.words
+    }
+
+    pub fn operation(&self, op: usize) -> &Operation {
+        &self.operations[op]
+    }
+
+    /// Every operation, by index
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// The index of the operation called `name`
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.operations.iter().position(|op| op.name == name)
+    }
+
+    /// The names of every instruction, `don't` before `do`
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = self.operations.iter().map(|op| op.name).collect();
+        names.extend(self.words().iter().map(|(word, _)| &word[..word.len() - 2]));
+        names
+    }
+
+    /// Where the instruction cut at the end of `tail` starts, if any: the earliest
+    /// position from which `tail` is the beginning of an instruction. Such an
+    /// instruction holds nothing but digits, commas, the opening parenthesis and
+    /// the letters of the names, so only the end of `tail` made of those is searched.
+    pub fn cut_instruction(&self, tail: &[u8]) -> Option<usize> {
+        let is_cut = |s: &[u8]| {
+            self.words
+                .iter()
+                .any(|(word, _)| s.len() < word.len() && word.as_bytes().starts_with(s))
+                || self.operations.iter().any(|op| op.is_cut(s))
+        };
+        let names = self.names();
+        let allowed = |b: u8| {
+            b.is_ascii_digit()
+                || b",(".contains(&b)
+                || names.iter().any(|name| name.as_bytes().contains(&b))
+        };
+
+        let start = tail.iter().rposition(|&b| !allowed(b)).map_or(0, |i| i + 1);
+        (start..tail.len()).find(|&i| is_cut(&tail[i..]))
+    }
+
+    /// Builds again what depends on the operations and the conditionals
+    fn rebuild(&mut self) {
+        self.words = vec![("don't()", Instruction::Dont), ("do()", Instruction::Do)];
+        if self.conditionals == Conditionals::Toggle {
+            self.words.push(("toggle()", Instruction::Toggle));
+        }
+        self.groups = (0..self.operations.len())
+            .map(|i| format!("op{i}"))
+            .collect();
+
+        let mut patterns: Vec<String> = self
+            .operations
+            .iter()
+            .enumerate()
+            .map(|(i, op)| op.pattern(i))
+            .collect();
+        patterns.extend(self.words.iter().map(|(word, _)| regex::escape(word)));
+        self.regex = Regex::new(&patterns.join("|")).unwrap();
+    }
+}
+
+/// An uncorrupted instruction of the program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction {
+    /// An operation, by its index in the [`InstructionSet`], and its arguments,
+    /// e.g. `mul(X,Y)` with `X` and `Y` being 1-3 digit numbers
+    Op { op: usize, args: Vec<u32> },
+    /// `do()`, enables the operations that follow
+    Do,
+    /// `don't()`, disables the operations that follow
+    Dont,
+    /// `toggle()`, enables the operations that follow if they were disabled,
+    /// and the other way around
+    Toggle,
+}
+
+/// An instruction found in the memory, at `offset` bytes from its start
+/// and `len` bytes long
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub offset: usize,
+    pub len: usize,
+    pub instruction: Instruction,
+}
+
+/// Scans the corrupted memory once, from start to end, yielding the instructions
+/// in the order they appear. Anything that is not exactly an instruction is skipped,
+/// bytes that are not text included.
+pub struct Tokenizer<'a> {
+    memory: &'a [u8],
+    set: &'a InstructionSet,
+    /// Only with [`Matcher::StateMachine`]
+    machine: Option<Machine>,
+    position: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(memory: &'a [u8], set: &'a InstructionSet) -> Self {
+        Tokenizer {
+            memory,
+            set,
+            machine: (set.matcher == Matcher::StateMachine).then(|| Machine::new(set)),
+            position: 0,
+        }
+    }
+
+    fn find_with_regex(&self) -> Option<Token> {
+        let captures = self.set.regex.captures_at(self.memory, self.position)?;
+        let found = captures.get(0).unwrap();
+
+        let op = (self.set.groups.iter().enumerate())
+            .find_map(|(i, group)| Some((i, captures.name(group)?)));
+        let instruction = match (op, found.as_bytes()) {
+            (Some((op, args)), _) => Instruction::Op {
+                op,
+                // Only digits and commas, so valid text
+                args: std::str::from_utf8(args.as_bytes())
+                    .unwrap()
+                    .split(',')
+                    .map(|n| n.parse().unwrap())
+                    .collect(),
+            },
+            (None, text) => {
+                let word = self
+                    .set
+                    .words
+                    .iter()
+                    .find(|(word, _)| word.as_bytes() == text);
+                word.unwrap().1.clone()
+            }
+        };
+        Some(Token {
+            offset: found.start(),
+            len: found.len(),
+            instruction,
+        })
+    }
+}
+
+impl Iterator for Tokenizer<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        let token = match &self.machine {
+            Some(machine) => machine.find_at(self.memory, self.position, self.set)?,
+            None => self.find_with_regex()?,
+        };
+        self.position = token.offset + token.len;
+        Some(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Instruction::*;
+
+    fn mul(a: u32, b: u32) -> Instruction {
+        Op {
+            op: 0,
+            args: vec![a, b],
+        }
+    }
+
+    #[test]
+    fn tokenizes_the_example() {
+        let memory = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+        let tokens: Vec<(usize, Instruction)> =
+            Tokenizer::new(memory.as_bytes(), &InstructionSet::default())
+                .map(|t| (t.offset, t.instruction))
+                .collect();
+
+        assert_eq!(
+            vec![
+                (1, mul(2, 4)),
+                (20, Dont),
+                (28, mul(5, 5)),
+                (48, mul(11, 8)),
+                (59, Do),
+                (64, mul(8, 5)),
+            ],
+            tokens
+        );
+    }
+
+    #[test]
+    fn skips_corrupted_instructions() {
+        let memory = "mul(4* mul(6,9! ?(12,34) mul ( 2 , 4 ) mul(1234,5) do( ) don't mul(0,007)";
+        let tokens: Vec<Instruction> =
+            Tokenizer::new(memory.as_bytes(), &InstructionSet::default())
+                .map(|t| t.instruction)
+                .collect();
+        assert_eq!(vec![mul(0, 7)], tokens);
+    }
+
+    #[test]
+    fn reads_only_ascii_digits() {
+        // `\d` would take these Arabic-Indic digits, which don't parse as a `u32`
+        let memory = "mul(\u{661}\u{662},3)mul(4,\u{665})mul(2,3)";
+        for matcher in [Matcher::Regex, Matcher::StateMachine] {
+            let set = InstructionSet {
+                matcher,
+                ..InstructionSet::default()
+            };
+            let tokens: Vec<Instruction> = Tokenizer::new(memory.as_bytes(), &set)
+                .map(|t| t.instruction)
+                .collect();
+            assert_eq!(vec![mul(2, 3)], tokens, "{matcher:?}");
+        }
+    }
+
+    #[test]
+    fn finds_cut_instructions() {
+        let mut set = InstructionSet::default();
+        set.register(Operation::builtin("max").unwrap());
+        let cut = |tail: &str| set.cut_instruction(tail.as_bytes());
+
+        assert_eq!(Some(3), cut("xyzmul(12,"));
+        assert_eq!(Some(1), cut("mmu"));
+        assert_eq!(Some(8), cut("mul(1,2)do"));
+        assert_eq!(Some(0), cut("don't("));
+        assert_eq!(Some(0), cut("max(1,2,3,4,5,6"));
+        assert_eq!(None, cut("mul(1,2)"));
+        assert_eq!(None, cut("mul(1,2,"));
+        assert_eq!(None, cut("mul(1234"));
+        assert_eq!(None, cut("mul(,"));
+        assert_eq!(None, cut("do()"));
+        assert_eq!(None, cut(""));
+    }
+
+    #[test]
+    fn recognizes_registered_operations() {
+        let mut set = InstructionSet::default();
+        for name in ["add", "max"] {
+            set.register(Operation::builtin(name).unwrap());
+        }
+        set.register(Operation {
+            digits: 1..=5,
+            ..Operation::builtin("mul").unwrap()
+        });
+
+        let memory = "add(1,2)max(7)max(1,2,3,4)max()add(1,2,3)sub(5,1)mul(12345,2)mul(1,123456)";
+        let tokens: Vec<(&str, Vec<u32>)> = Tokenizer::new(memory.as_bytes(), &set)
+            .map(|t| match t.instruction {
+                Op { op, args } => (set.operation(op).name, args),
+                _ => unreachable!(),
+            })
+            .collect();
+
+        assert_eq!(
+            vec![
+                ("add", vec![1, 2]),
+                ("max", vec![7]),
+                ("max", vec![1, 2, 3, 4]),
+                ("mul", vec![12345, 2]),
+            ],
+            tokens
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Operations have a name")]
+    fn rejects_operations_without_a_name() {
+        let mut set = InstructionSet::default();
+        set.register(Operation::new("", |_| Some(0)));
+    }
+
+    #[test]
+    fn compiles_the_largest_arities() {
+        let mut set = InstructionSet::default();
+        for name in ["mul", "add", "sub", "div", "max"] {
+            set.register(Operation {
+                arity: 1..=Operation::MAX_ARITY,
+                digits: 1..=9,
+                ..Operation::builtin(name).unwrap()
+            });
+        }
+        let memory = format!("add({})", ["999999999"; Operation::MAX_ARITY].join(","));
+        assert_eq!(1, Tokenizer::new(memory.as_bytes(), &set).count());
+    }
+
+    #[test]
+    fn runs_any_number_of_arguments() {
+        let mut set = InstructionSet::default();
+        for name in ["mul", "add", "sub", "div", "max"] {
+            set.register(Operation {
+                arity: 1..=4,
+                ..Operation::builtin(name).unwrap()
+            });
+        }
+        let eval = |name: &str, args: &[u32]| (set.operation(set.find(name).unwrap()).eval)(args);
+
+        assert_eq!(
+            [Some(5), Some(5), Some(5), Some(5), Some(5)],
+            ["mul", "add", "sub", "div", "max"].map(|name| eval(name, &[5]))
+        );
+        assert_eq!(Some(20 - 3 - 4 - 5), eval("sub", &[20, 3, 4, 5]));
+        assert_eq!(Some(0), eval("sub", &[5, 3, 4]));
+        assert_eq!(Some(100 / 5 / 2), eval("div", &[100, 5, 2]));
+        assert_eq!(Some(0), eval("div", &[100, 0, 2]));
+
+        let memory = "sub(5)div(7)sub(9,2,3)div(60,2,3,5)mul(2,3,4)";
+        let result = crate::interpreter::run(memory, &set).unwrap();
+        assert_eq!(5 + 7 + 4 + 2 + 24, result.total);
+    }
+
+    #[test]
+    #[should_panic(expected = "Operations take at most 1000 arguments")]
+    fn rejects_arities_too_large_to_compile() {
+        let mut set = InstructionSet::default();
+        set.register(Operation {
+            arity: 1..=100_000,
+            ..Operation::builtin("max").unwrap()
+        });
+    }
+}
@@ -0,0 +1,204 @@
+use std::fmt;
+
+use crate::instruction::{Conditionals, Instruction, InstructionSet, Tokenizer};
+
+/// An operation whose result does not fit, or that makes a sum too large
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overflow {
+    pub name: &'static str,
+    pub args: Vec<u32>,
+}
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let args: Vec<String> = self.args.iter().map(|a| a.to_string()).collect();
+        write!(f, "overflow at {}({})", self.name, args.join(","))
+    }
+}
+
+impl std::error::Error for Overflow {}
+
+/// Runs the instructions one after the other, keeping the sum of the results of
+/// all the operations and the one of the operations that were enabled.
+/// The sums are wide enough for any realistic memory, and checked anyway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interpreter {
+    pub enabled: bool,
+    /// Number of `don't()` not matched by a `do()` yet, with [`Conditionals::Nested`]
+    pub depth: usize,
+    pub total: u128,
+    pub enabled_total: u128,
+}
+
+impl Interpreter {
+    /// An interpreter at the beginning of the program, where the operations are
+    /// enabled unless the set says otherwise
+    pub fn new(set: &InstructionSet) -> Self {
+        Interpreter {
+            enabled: set.start_enabled,
+            // Starting disabled takes a `do()` to enable, as after a `don't()`
+            depth: !set.start_enabled as usize,
+            total: 0,
+            enabled_total: 0,
+        }
+    }
+
+    /// Follows a conditional instruction under the rules of the set
+    pub fn apply_conditional(&mut self, set: &InstructionSet, instruction: &Instruction) {
+        match (instruction, set.conditionals()) {
+            (Instruction::Do, Conditionals::Nested) => {
+                self.depth = self.depth.saturating_sub(1);
+                self.enabled = self.depth == 0;
+            }
+            (Instruction::Dont, Conditionals::Nested) => {
+                self.depth += 1;
+                self.enabled = false;
+            }
+            (Instruction::Do, _) => self.enabled = true,
+            (Instruction::Dont, _) => self.enabled = false,
+            (Instruction::Toggle, _) => self.enabled = !self.enabled,
+            (Instruction::Op { .. }, _) => (),
+        }
+    }
+
+    /// Runs the instruction, leaving the sums as they were on overflow
+    pub fn execute(
+        &mut self,
+        set: &InstructionSet,
+        instruction: &Instruction,
+    ) -> Result<(), Overflow> {
+        match instruction {
+            Instruction::Op { op, args } => {
+                let operation = set.operation(*op);
+                let overflow = || Overflow {
+                    name: operation.name,
+                    args: args.clone(),
+                };
+
+                let result = (operation.eval)(args).ok_or_else(overflow)? as u128;
+                let total = self.total.checked_add(result).ok_or_else(overflow)?;
+                if self.enabled {
+                    self.enabled_total = self
+                        .enabled_total
+                        .checked_add(result)
+                        .ok_or_else(overflow)?;
+                }
+                self.total = total;
+            }
+            _ => self.apply_conditional(set, instruction),
+        }
+        Ok(())
+    }
+}
+
+/// Runs every instruction of the corrupted memory, both parts of the puzzle
+/// being answered from this single pass
+pub fn run(memory: &str, set: &InstructionSet) -> Result<Interpreter, Overflow> {
+    let mut interpreter = Interpreter::new(set);
+    for token in Tokenizer::new(memory.as_bytes(), set) {
+        interpreter.execute(set, &token.instruction)?;
+    }
+    Ok(interpreter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::Operation;
+    use Instruction::*;
+
+    #[test]
+    fn follows_the_last_conditional() {
+        let set = InstructionSet::default();
+        let mul = |a, b| Op {
+            op: 0,
+            args: vec![a, b],
+        };
+        let mut interpreter = Interpreter::new(&set);
+        for instruction in [mul(2, 3), Dont, mul(4, 5), Dont, Do, Do, mul(1, 7), Dont] {
+            interpreter.execute(&set, &instruction).unwrap();
+        }
+        assert_eq!(
+            (false, 33, 13),
+            (
+                interpreter.enabled,
+                interpreter.total,
+                interpreter.enabled_total
+            )
+        );
+    }
+
+    #[test]
+    fn follows_other_conditionals() {
+        let memory = "mul(1,1)don't()don't()mul(2,1)do()mul(4,1)do()do()mul(8,1)\
+                      toggle()mul(16,1)toggle()mul(32,1)";
+        let sum = |conditionals, start_enabled| {
+            let mut set = InstructionSet::default();
+            set.set_conditionals(conditionals);
+            set.start_enabled = start_enabled;
+            run(memory, &set).unwrap().enabled_total
+        };
+
+        // `toggle()` is nothing but corrupted memory unless it is asked for
+        assert_eq!(1 + 4 + 8 + 16 + 32, sum(Conditionals::Switch, true));
+        assert_eq!(4 + 8 + 16 + 32, sum(Conditionals::Switch, false));
+        // Two `don't()` take two `do()`, starting disabled counts as one more `don't()`
+        assert_eq!(1 + 8 + 16 + 32, sum(Conditionals::Nested, true));
+        assert_eq!(8 + 16 + 32, sum(Conditionals::Nested, false));
+        assert_eq!(1 + 4 + 8 + 32, sum(Conditionals::Toggle, true));
+        assert_eq!(4 + 8 + 32, sum(Conditionals::Toggle, false));
+    }
+
+    #[test]
+    fn accumulates_every_operation() {
+        let mut set = InstructionSet::default();
+        for name in ["add", "sub", "div", "max"] {
+            set.register(Operation::builtin(name).unwrap());
+        }
+        let memory = "mul(2,3)add(4,5)don't()sub(9,2)sub(2,9)do()div(7,2)div(7,0)max(3,8,1)";
+        let result = run(memory, &set).unwrap();
+        assert_eq!(
+            (6 + 9 + 7 + 3 + 8, 6 + 9 + 3 + 8),
+            (result.total, result.enabled_total)
+        );
+    }
+
+    #[test]
+    fn sums_beyond_u32() {
+        let memory = "mul(999,999)".repeat(5000) + "don't()" + &"mul(998,997)".repeat(5000);
+        let result = run(&memory, &InstructionSet::default()).unwrap();
+        assert_eq!(999 * 999 * 5000, result.enabled_total);
+        assert_eq!(999 * 999 * 5000 + 998 * 997 * 5000, result.total);
+        assert!(result.enabled_total > u32::MAX as u128);
+    }
+
+    #[test]
+    fn detects_overflows() {
+        let mut set = InstructionSet::default();
+        set.register(Operation {
+            arity: 2..=3,
+            digits: 1..=9,
+            ..Operation::builtin("mul").unwrap()
+        });
+        let result = run("mul(999999999,999999999)", &set).unwrap();
+        assert_eq!(999_999_999 * 999_999_999, result.total);
+
+        let err = run("mul(2,3)mul(999999999,999999999,999)", &set).unwrap_err();
+        assert_eq!("overflow at mul(999999999,999999999,999)", err.to_string());
+
+        let mut interpreter = Interpreter {
+            total: u128::MAX - 6,
+            ..Interpreter::new(&set)
+        };
+        let mul = |a, b| Op {
+            op: 0,
+            args: vec![a, b],
+        };
+        assert_eq!(Ok(()), interpreter.execute(&set, &mul(2, 3)));
+        assert!(interpreter.execute(&set, &mul(1, 1)).is_err());
+        assert_eq!(
+            (u128::MAX, 6),
+            (interpreter.total, interpreter.enabled_total)
+        );
+    }
+}
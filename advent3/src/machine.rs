@@ -0,0 +1,173 @@
+use crate::instruction::{Instruction, InstructionSet, Operation, Token};
+
+/// Where the matcher is within an operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// This many bytes of the name were read
+    Name(usize),
+    /// The name was read, the opening parenthesis is next
+    Open,
+    /// Within an argument, after this many digits
+    Number(usize),
+}
+
+/// A matcher reading the memory one byte at a time, without the `regex` crate.
+/// It recognizes the same instructions as the pattern of the [`InstructionSet`],
+/// trying them in the same order at each position, so both always agree.
+pub struct Machine {
+    /// Whether an instruction may start with the byte
+    starts: [bool; 256],
+}
+
+impl Machine {
+    pub fn new(set: &InstructionSet) -> Self {
+        let mut starts = [false; 256];
+        for name in set.names() {
+            starts[name.as_bytes()[0] as usize] = true;
+        }
+        Machine { starts }
+    }
+
+    /// The first instruction found at or after `start`
+    pub fn find_at(&self, memory: &[u8], start: usize, set: &InstructionSet) -> Option<Token> {
+        for offset in start..memory.len() {
+            if !self.starts[memory[offset] as usize] {
+                continue;
+            }
+            let rest = &memory[offset..];
+
+            let found = (set.operations().iter().enumerate())
+                .find_map(|(op, operation)| {
+                    let (len, args) = run(rest, operation)?;
+                    Some((len, Instruction::Op { op, args }))
+                })
+                .or_else(|| {
+                    let (word, instruction) = set
+                        .words()
+                        .iter()
+                        .find(|(word, _)| rest.starts_with(word.as_bytes()))?;
+                    Some((word.len(), instruction.clone()))
+                });
+            if let Some((len, instruction)) = found {
+                return Some(Token {
+                    offset,
+                    len,
+                    instruction,
+                });
+            }
+        }
+        None
+    }
+}
+
+/// Runs the state machine of `op` on the start of `memory`, giving the length
+/// of the instruction and its arguments if it is one
+fn run(memory: &[u8], op: &Operation) -> Option<(usize, Vec<u32>)> {
+    let name = op.name.as_bytes();
+    let mut state = State::Name(0);
+    let mut args: Vec<u32> = Vec::new();
+    let mut arg: u32 = 0;
+
+    for (i, &b) in memory.iter().enumerate() {
+        state = match (state, b) {
+            (State::Name(n), _) if b == name[n] => {
+                if n + 1 == name.len() {
+                    State::Open
+                } else {
+                    State::Name(n + 1)
+                }
+            }
+            (State::Open, b'(') => State::Number(0),
+            (State::Number(digits), b'0'..=b'9') if digits < *op.digits.end() => {
+                arg = arg * 10 + (b - b'0') as u32;
+                State::Number(digits + 1)
+            }
+            (State::Number(digits), b',' | b')') if op.digits.contains(&digits) => {
+                args.push(arg);
+                arg = 0;
+                if b == b')' {
+                    return op.arity.contains(&args.len()).then_some((i + 1, args));
+                }
+                if args.len() == *op.arity.end() {
+                    return None;
+                }
+                State::Number(0)
+            }
+            _ => return None,
+        };
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::{Conditionals, Matcher, Tokenizer};
+
+    #[test]
+    fn agrees_with_the_regex() {
+        let mut seed: u32 = 44;
+        let mut next = |n: u32| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed % n
+        };
+        let pieces: [&[u8]; 21] = [
+            b"mul(",
+            b"mul",
+            b"do()",
+            b"don't()",
+            b"don't",
+            b"do(",
+            b"toggle()",
+            b"max(",
+            b"add(",
+            b"1",
+            b"23",
+            b"456",
+            b"0",
+            b",",
+            b")",
+            b"(",
+            b"x",
+            b"m",
+            b"d",
+            "\u{663}".as_bytes(),
+            b"\xff",
+        ];
+
+        let mut sets = vec![InstructionSet::default(), InstructionSet::default()];
+        sets[1].register(Operation::builtin("max").unwrap());
+        sets[1].register(Operation {
+            digits: 2..=4,
+            ..Operation::builtin("add").unwrap()
+        });
+        sets[1].set_conditionals(Conditionals::Toggle);
+
+        for _ in 0..3000 {
+            let memory: Vec<u8> = (0..next(40))
+                .flat_map(|_| pieces[next(pieces.len() as u32) as usize].to_vec())
+                .collect();
+
+            for set in &mut sets {
+                set.matcher = Matcher::Regex;
+                let expected: Vec<Token> = Tokenizer::new(&memory, set).collect();
+                set.matcher = Matcher::StateMachine;
+                let found: Vec<Token> = Tokenizer::new(&memory, set).collect();
+                assert_eq!(expected, found, "{}", String::from_utf8_lossy(&memory));
+            }
+        }
+    }
+
+    #[test]
+    fn stops_at_the_first_byte_out_of_place() {
+        let mul = Operation::builtin("mul").unwrap();
+        assert_eq!(Some((9, vec![12, 3])), run(b"mul(12,3)x", &mul));
+        assert_eq!(None, run(b"mul(1234,5)", &mul));
+        assert_eq!(None, run(b"mul(1,2,3)", &mul));
+        assert_eq!(None, run(b"mul(,2)", &mul));
+        assert_eq!(None, run(b"mul(1,2", &mul));
+        assert_eq!(None, run(b"mu", &mul));
+    }
+}
@@ -1,5 +1,19 @@
-use regex::Regex;
-use std::fs;
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::process;
+use std::time::Instant;
+
+mod diagnostics;
+mod dump;
+mod instruction;
+mod interpreter;
+mod machine;
+mod stream;
+
+use diagnostics::Reason;
+use instruction::{InstructionSet, Matcher, Operation, Token, Tokenizer};
+use interpreter::Overflow;
 
 /// "Our computers are having issues,
 /// so I have no idea if we have any Chief Historians
@@ -33,25 +47,11 @@ use std::fs;
 ///
 /// Scan the corrupted memory for uncorrupted mul instructions.
 /// What do you get if you add up all of the results of the multiplications?
-pub fn do_sum_mul(input: String) -> u32 {
-    // Create the regex rule of mul
-    // mul(123,123)
-    let r = Regex::new(r"mul\((\d{1,3})\,(\d{1,3})\)").unwrap();
-
-    // Initialize the vector storing numbers
-    let mut muls: Vec<(u32, u32)> = Vec::new();
-
-    // Apply the regex and store the numbers in the vector
-    r.captures_iter(input.as_str())
-        .map(|e| e.extract())
-        .for_each(|(_, [n1, n2])| muls.push((n1.parse().unwrap(), n2.parse().unwrap())));
-
-    // Return the sum of multiplications
-    muls.iter()
-        .map(|(n1, n2)| n1 * n2)
-        .collect::<Vec<u32>>()
-        .iter()
-        .sum::<u32>()
+pub fn do_sum_mul(input: String) -> u128 {
+    // A `mul` of two 1-3 digit numbers never overflows
+    interpreter::run(&input, &InstructionSet::default())
+        .unwrap()
+        .total
 }
 
 /// As you scan through the corrupted memory, you notice that
@@ -78,29 +78,156 @@ pub fn do_sum_mul(input: String) -> u32 {
 ///
 /// Handle the new instructions; what do you get if you add up all
 /// of the results of just the enabled multiplications?
-pub fn do_sum_mul_with_do(input: String) -> u32 {
-    // Split by `don't()`
-    let split_input_dont: Vec<&str> = input.split("don't()").collect();
-
-    // Initialize a vector for `do()` split
-    let mut split_input_do: Vec<&str> = Vec::new();
-
-    // Add the first element until `don't()` to the vector
-    split_input_do.push(split_input_dont.first().unwrap());
+pub fn do_sum_mul_with_do(input: String) -> u128 {
+    // Instructions are matched in the memory as it is, never in enabled regions
+    // joined together, so none can be made of text around a disabled region
+    interpreter::run(&input, &InstructionSet::default())
+        .unwrap()
+        .enabled_total
+}
 
-    // Add the elements after `do()` and before the next `don't()` to the vector
-    for s in split_input_dont.iter().skip(1) {
-        s.split("do()").skip(1).for_each(|e| split_input_do.push(e));
+/// How much a lenient sum gained over the strict one, signed as it can also lose
+/// what a `don't ()` read leniently disables
+fn difference(lenient: u128, strict: u128) -> String {
+    match lenient.checked_sub(strict) {
+        Some(gain) => format!("+{gain}"),
+        None => format!("-{}", strict - lenient),
     }
+}
 
-    // Sum of multiply of the substring of input
-    do_sum_mul(split_input_do.concat())
+/// Prints the operation that overflowed and stops
+fn exit_on_overflow<T>(result: Result<T, Overflow>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("Could not run the memory: {e}");
+        process::exit(1);
+    })
 }
 
+/// Usage: `advent3 [--ops add,sub,div,max] [--stream FILE] [--chunk-size N] [--dump]
+/// [--diagnose] [--lenient] [--matcher regex|machine] [--bench N]
+/// [--conditionals switch|nested|toggle] [--start-disabled]`
+///
+/// With `--ops`, the listed operations are run along with `mul`. With `--stream`,
+/// the memory is read from `FILE` (`-` for the standard input) `N` bytes at a time
+/// instead of all at once. With `--dump`, the memory is printed with what was
+/// picked up in it highlighted, followed by the list of the instructions.
+/// With `--diagnose`, the near misses are counted by reason, with their offsets.
+/// With `--lenient`, the near misses with spaces or long numbers are run too,
+/// and the sums are printed along with what they gained. With `--matcher machine`,
+/// the instructions are found by a hand-written state machine instead of a regex.
+/// With `--bench`, both are timed over `N` scans of the memory. With `--conditionals`,
+/// `do()` and `don't()` follow other rules, see [`instruction::Conditionals`], and with
+/// `--start-disabled` the operations are disabled until the first `do()`.
 fn main() {
+    let mut set = InstructionSet::default();
+    let mut stream: Option<String> = None;
+    let mut chunk_size: usize = 64 * 1024;
+    let mut dump = false;
+    let mut diagnose = false;
+    let mut lenient = false;
+    let mut bench: Option<u32> = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| panic!("Missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--ops" => {
+                for name in value().split(',') {
+                    let op = Operation::builtin(name)
+                        .unwrap_or_else(|| panic!("Unknown operation: {name}"));
+                    set.register(op);
+                }
+            }
+            "--stream" => stream = Some(value()),
+            "--chunk-size" => chunk_size = value().parse().unwrap(),
+            "--dump" => dump = true,
+            "--diagnose" => diagnose = true,
+            "--lenient" => lenient = true,
+            "--matcher" => set.matcher = value().parse().unwrap(),
+            "--bench" => bench = Some(value().parse().unwrap()),
+            "--conditionals" => set.set_conditionals(value().parse().unwrap()),
+            "--start-disabled" => set.start_enabled = false,
+            _ => panic!("Unknown argument: {arg}"),
+        }
+    }
+
+    if let Some(path) = stream {
+        let result = match path.as_str() {
+            "-" => stream::run_stream(io::stdin(), &set, chunk_size),
+            _ => stream::run_stream(File::open(path).unwrap(), &set, chunk_size),
+        }
+        .unwrap_or_else(|e| {
+            eprintln!("Could not run the memory: {e}");
+            process::exit(1);
+        });
+        println!("Sum: {}", result.total);
+        println!("Sum with do: {}", result.enabled_total);
+        return;
+    }
+
     let input: String = fs::read_to_string("input.txt").expect("Could not read the puzzle input");
-    println!("Sum: {}", do_sum_mul(input.clone()));
-    println!("Sum with do: {}", do_sum_mul_with_do(input.clone()));
+    if let Some(runs) = bench {
+        for matcher in [Matcher::Regex, Matcher::StateMachine] {
+            let mut set = set.clone();
+            set.matcher = matcher;
+            let start = Instant::now();
+            let mut tokens = 0;
+            for _ in 0..runs {
+                tokens += Tokenizer::new(input.as_bytes(), &set).count();
+            }
+            let elapsed = start.elapsed().as_secs_f64();
+            println!(
+                "{matcher:?}: {tokens} instructions in {elapsed:.3} s, {:.1} MB/s",
+                (input.len() as f64 * runs as f64) / elapsed / 1e6
+            );
+        }
+        return;
+    }
+
+    if dump {
+        println!("{}", dump::annotate(input.as_bytes(), &set));
+        for line in dump::listing(input.as_bytes(), &set) {
+            println!("{line}");
+        }
+    }
+
+    // Both parts come from a single run over the instructions
+    let result = exit_on_overflow(interpreter::run(&input, &set));
+    println!("Sum: {}", result.total);
+    println!("Sum with do: {}", result.enabled_total);
+
+    if diagnose {
+        let memory = input.as_bytes();
+        let tokens: Vec<Token> = Tokenizer::new(memory, &set).collect();
+        let misses = diagnostics::near_misses(memory, &set, &tokens);
+        for reason in Reason::ALL {
+            let offsets: Vec<String> = misses
+                .iter()
+                .filter(|miss| miss.reason == reason)
+                .map(|miss| miss.offset.to_string())
+                .collect();
+            match offsets.len() {
+                0 => println!("{reason}: 0"),
+                n => println!("{reason}: {n} at {}", offsets.join(", ")),
+            }
+        }
+    }
+    if lenient {
+        let lenient = exit_on_overflow(diagnostics::run_lenient(input.as_bytes(), &set));
+        println!(
+            "Lenient sum: {} ({})",
+            lenient.total,
+            difference(lenient.total, result.total)
+        );
+        println!(
+            "Lenient sum with do: {} ({})",
+            lenient.enabled_total,
+            difference(lenient.enabled_total, result.enabled_total)
+        );
+    }
 }
 
 #[cfg(test)]
@@ -111,7 +238,7 @@ mod tests {
     fn given_test_part_one() {
         let input: String =
             fs::read_to_string("test_input.txt").expect("Could not read the puzzle input");
-        let e: u32 = 161;
+        let e: u128 = 161;
         assert_eq!(e, do_sum_mul(input))
     }
 
@@ -119,7 +246,49 @@ mod tests {
     fn given_test_part_two() {
         let input: String =
             fs::read_to_string("test_input.txt").expect("Could not read the puzzle input");
-        let e: u32 = 48;
+        let e: u128 = 48;
         assert_eq!(e, do_sum_mul_with_do(input))
     }
+
+    /// The evaluation as it was first written, joining the enabled regions
+    fn do_sum_mul_with_do_joined(input: &str) -> u128 {
+        let split_input_dont: Vec<&str> = input.split("don't()").collect();
+        let mut split_input_do: Vec<&str> = vec![split_input_dont[0]];
+        for s in split_input_dont.iter().skip(1) {
+            s.split("do()").skip(1).for_each(|e| split_input_do.push(e));
+        }
+        do_sum_mul(split_input_do.concat())
+    }
+
+    #[test]
+    fn no_instruction_across_disabled_regions() {
+        // Joining what is around a disabled region made up these instructions
+        let fused = [
+            ("mudon't()xdo()l(2,3)", 6),
+            ("xmul(4,don't()_mul(1,1)do()5)", 20),
+            ("mul(don't()do()7,8)", 56),
+            ("mul(1don't()mul(9,9)do()2,3)", 36),
+        ];
+        for (memory, joined) in fused {
+            assert_eq!(joined, do_sum_mul_with_do_joined(memory), "{memory}");
+            assert_eq!(0, do_sum_mul_with_do(memory.to_string()), "{memory}");
+        }
+
+        // Nor across chunks of a stream
+        let memory = "mul(1,2)don't()mul(3,4)do()mul(5,6)";
+        for chunk_size in 1..memory.len() {
+            let result =
+                stream::run_stream(memory.as_bytes(), &InstructionSet::default(), chunk_size)
+                    .unwrap();
+            assert_eq!((44, 32), (result.total, result.enabled_total));
+        }
+    }
+
+    #[test]
+    fn lenient_sums_can_be_lower() {
+        assert_eq!("+8", difference(14, 6));
+        assert_eq!("+0", difference(6, 6));
+        assert_eq!("-25", difference(6, 31));
+        assert_eq!(format!("-{}", u128::MAX), difference(0, u128::MAX));
+    }
 }
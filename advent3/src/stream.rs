@@ -0,0 +1,120 @@
+use std::error::Error;
+use std::io::{ErrorKind, Read};
+
+use crate::instruction::{InstructionSet, Token, Tokenizer};
+use crate::interpreter::Interpreter;
+
+/// Scans the memory from `reader` `chunk_size` bytes at a time, calling `f` on
+/// every instruction, with its offset from the start of the stream, until it fails.
+///
+/// An instruction cut at the end of a chunk is kept for the next one, so only the
+/// current chunk and the beginning of at most one instruction are ever held.
+/// That is bounded by the longest instruction of the set, whatever the size of
+/// the memory, except with an operation taking any number of arguments, such as
+/// `max`: an unclosed `max(1,1,1,...` is kept whole, as it may still be closed.
+pub fn scan_stream<R, F>(
+    mut reader: R,
+    set: &InstructionSet,
+    chunk_size: usize,
+    mut f: F,
+) -> Result<(), Box<dyn Error>>
+where
+    R: Read,
+    F: FnMut(Token) -> Result<(), Box<dyn Error>>,
+{
+    let mut chunk = vec![0; chunk_size.max(1)];
+    let mut buffer: Vec<u8> = Vec::new();
+    // Offset of the start of the buffer in the stream
+    let mut offset = 0;
+
+    loop {
+        let n = match reader.read(&mut chunk) {
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        buffer.extend_from_slice(&chunk[..n]);
+
+        let mut end = 0;
+        for token in Tokenizer::new(&buffer, set) {
+            end = token.offset + token.len;
+            f(Token {
+                offset: offset + token.offset,
+                ..token
+            })?;
+        }
+        if n == 0 {
+            return Ok(());
+        }
+
+        // Instructions end with a parenthesis, so none that was found can be cut
+        let keep = match set.cut_instruction(&buffer[end..]) {
+            Some(i) => end + i,
+            None => buffer.len(),
+        };
+        buffer.drain(..keep);
+        offset += keep;
+    }
+}
+
+/// Runs every instruction of the memory from `reader`, the state of the
+/// interpreter going from one chunk to the next
+pub fn run_stream<R: Read>(
+    reader: R,
+    set: &InstructionSet,
+    chunk_size: usize,
+) -> Result<Interpreter, Box<dyn Error>> {
+    let mut interpreter = Interpreter::new(set);
+    scan_stream(reader, set, chunk_size, |token| {
+        Ok(interpreter.execute(set, &token.instruction)?)
+    })?;
+    Ok(interpreter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::Operation;
+    use crate::interpreter::run;
+    use std::fs;
+
+    #[test]
+    fn same_result_whatever_the_chunks() {
+        let mut set = InstructionSet::default();
+        set.register(Operation::builtin("max").unwrap());
+        let input: String =
+            fs::read_to_string("test_input.txt").expect("Could not read the puzzle input");
+        let memory = input.repeat(3) + "max(1,2,3,4,5,6,7,8,9)do_don't(mul(1,2)don't()mul(3,4)";
+        let expected: Vec<Token> = Tokenizer::new(memory.as_bytes(), &set).collect();
+
+        for chunk_size in [1, 2, 3, 5, 7, 16, 64, 1000] {
+            let mut tokens: Vec<Token> = Vec::new();
+            scan_stream(memory.as_bytes(), &set, chunk_size, |t| {
+                tokens.push(t);
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(expected, tokens, "chunks of {chunk_size}");
+
+            let result = run_stream(memory.as_bytes(), &set, chunk_size).unwrap();
+            assert_eq!(run(&memory, &set).unwrap(), result);
+        }
+    }
+
+    #[test]
+    fn keeps_the_state_across_chunks() {
+        // The `don't()` and the `mul` it disables end up in different chunks
+        let memory = b"mul(2,3)don't()xxxxxxxxxxxmul(4,5)\xffdo()mul(1,1)";
+        let result = run_stream(&memory[..], &InstructionSet::default(), 8).unwrap();
+        assert_eq!((27, 7), (result.total, result.enabled_total));
+    }
+
+    #[test]
+    fn sums_beyond_u32_in_constant_memory() {
+        // 140 kB read 100 bytes at a time: 10000 products of 998001 add up to
+        // 9980010000, over twice `u32::MAX`, with only a chunk held at once
+        let memory = "mul(999,999)xx".repeat(10000);
+        let result = run_stream(memory.as_bytes(), &InstructionSet::default(), 100).unwrap();
+        assert_eq!(9_980_010_000, result.total);
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+
+/// What to draw of the rule graph
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DotOptions {
+    /// An update whose pages are the only ones drawn, and the rules it breaks in red
+    pub update: Option<Vec<u8>>,
+    /// Draws every page of the rules even with an update
+    pub all_pages: bool,
+    /// Leaves out the rules following from the others
+    pub reduce: bool,
+}
+
+/// Whether `to` can be reached from `from` without taking the rule from one to the other
+fn reachable_without(graph: &BTreeMap<u8, BTreeSet<u8>>, from: u8, to: u8) -> bool {
+    let mut seen = BTreeSet::from([from]);
+    let mut stack = vec![from];
+    while let Some(page) = stack.pop() {
+        for &next in graph.get(&page).into_iter().flatten() {
+            if page == from && next == to {
+                continue;
+            }
+            if next == to {
+                return true;
+            }
+            if seen.insert(next) {
+                stack.push(next);
+            }
+        }
+    }
+    false
+}
+
+/// Drops the rules one at a time, as long as every page can still be reached from
+/// the same ones, except for the `kept` ones. Without cycles, this is the transitive
+/// reduction; with some, the rules kept depend on the order they are looked at,
+/// and none of them can go.
+fn reduce(graph: &mut BTreeMap<u8, BTreeSet<u8>>, kept: &BTreeSet<(u8, u8)>) {
+    let edges: Vec<(u8, u8)> = graph
+        .iter()
+        .flat_map(|(&from, afters)| afters.iter().map(move |&to| (from, to)))
+        .collect();
+    for (from, to) in edges {
+        if from != to && !kept.contains(&(from, to)) && reachable_without(graph, from, to) {
+            graph.get_mut(&from).unwrap().remove(&to);
+        }
+    }
+}
+
+/// The rules as a Graphviz graph, an edge from `X` to `Y` for each rule `X|Y`.
+///
+/// With an update, a rule whose pages it prints in the wrong order is drawn in red.
+/// Those are never left out by the reduction, even when they follow from rules
+/// going through pages outside of the update, which can't be red.
+pub fn to_dot(rules: &HashMap<u8, BTreeSet<u8>>, options: &DotOptions) -> String {
+    let positions: HashMap<u8, usize> = options
+        .update
+        .iter()
+        .flatten()
+        .enumerate()
+        .map(|(i, &page)| (page, i))
+        .collect();
+    let drawn =
+        |page: &u8| options.update.is_none() || options.all_pages || positions.contains_key(page);
+
+    let mut graph: BTreeMap<u8, BTreeSet<u8>> = BTreeMap::new();
+    for (before, afters) in rules.iter().filter(|(before, _)| drawn(before)) {
+        graph.entry(*before).or_default();
+        for after in afters.iter().filter(|after| drawn(after)) {
+            graph.entry(*after).or_default();
+            graph.get_mut(before).unwrap().insert(*after);
+        }
+    }
+    let broken: BTreeSet<(u8, u8)> = graph
+        .iter()
+        .flat_map(|(&before, afters)| afters.iter().map(move |&after| (before, after)))
+        .filter(|(before, after)| {
+            matches!(
+                (positions.get(before), positions.get(after)),
+                (Some(i), Some(j)) if j < i
+            )
+        })
+        .collect();
+    if options.reduce {
+        reduce(&mut graph, &broken);
+    }
+
+    let mut dot = String::from("digraph rules {\n");
+    for page in graph.keys() {
+        match positions.get(page) {
+            Some(i) => writeln!(dot, "    {page} [label=\"{page} ({})\"];", i + 1).unwrap(),
+            None => writeln!(dot, "    {page};").unwrap(),
+        }
+    }
+    for (before, afters) in &graph {
+        for after in afters {
+            if broken.contains(&(*before, *after)) {
+                writeln!(dot, "    {before} -> {after} [color=red];").unwrap();
+            } else {
+                writeln!(dot, "    {before} -> {after};").unwrap();
+            }
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_rules;
+
+    #[test]
+    fn draws_every_rule() {
+        let rules = get_rules(vec!["47|53", "97|13", "47|13"]);
+        assert_eq!(
+            "digraph rules {\n    13;\n    47;\n    53;\n    97;\n    \
+             47 -> 13;\n    47 -> 53;\n    97 -> 13;\n}\n",
+            to_dot(&rules, &DotOptions::default())
+        );
+    }
+
+    #[test]
+    fn highlights_the_broken_rules_of_an_update() {
+        let rules = get_rules(vec!["29|13", "75|29", "75|13", "61|13"]);
+        let mut options = DotOptions {
+            update: Some(vec![13, 75, 29]),
+            ..DotOptions::default()
+        };
+        assert_eq!(
+            "digraph rules {\n    \
+             13 [label=\"13 (1)\"];\n    29 [label=\"29 (3)\"];\n    75 [label=\"75 (2)\"];\n    \
+             29 -> 13 [color=red];\n    75 -> 13 [color=red];\n    75 -> 29;\n}\n",
+            to_dot(&rules, &options)
+        );
+
+        options.reduce = true;
+        options.all_pages = true;
+        assert_eq!(
+            "digraph rules {\n    \
+             13 [label=\"13 (1)\"];\n    29 [label=\"29 (3)\"];\n    61;\n    75 [label=\"75 (2)\"];\n    \
+             29 -> 13 [color=red];\n    61 -> 13;\n    75 -> 13 [color=red];\n    75 -> 29;\n}\n",
+            to_dot(&rules, &options)
+        );
+
+        // 75|13 follows from 75|29 and 29|13, but 29 isn't in the update to be red
+        let rules = get_rules(vec!["75|29", "29|13", "75|13"]);
+        let options = DotOptions {
+            update: Some(vec![13, 75]),
+            all_pages: true,
+            reduce: true,
+        };
+        assert_eq!(
+            "digraph rules {\n    \
+             13 [label=\"13 (1)\"];\n    29;\n    75 [label=\"75 (2)\"];\n    \
+             29 -> 13;\n    75 -> 13 [color=red];\n    75 -> 29;\n}\n",
+            to_dot(&rules, &options)
+        );
+    }
+
+    #[test]
+    fn keeps_what_can_be_reached() {
+        let mut next = crate::tests::rng(49);
+        let reach = |graph: &BTreeMap<u8, BTreeSet<u8>>, from: u8| {
+            let mut seen = BTreeSet::new();
+            let mut stack = vec![from];
+            while let Some(page) = stack.pop() {
+                for &next in graph.get(&page).into_iter().flatten() {
+                    if seen.insert(next) {
+                        stack.push(next);
+                    }
+                }
+            }
+            seen
+        };
+
+        for _ in 0..300 {
+            // Cycles included
+            let mut graph: BTreeMap<u8, BTreeSet<u8>> = BTreeMap::new();
+            for _ in 0..next(30) {
+                let (a, b) = (next(10) as u8, next(10) as u8);
+                graph.entry(b).or_default();
+                graph.entry(a).or_default().insert(b);
+            }
+            let mut reduced = graph.clone();
+            reduce(&mut reduced, &BTreeSet::new());
+
+            for &page in graph.keys() {
+                assert_eq!(reach(&graph, page), reach(&reduced, page), "{graph:?}");
+            }
+            // Not a single rule more than needed
+            for (&from, afters) in &reduced {
+                for &to in afters {
+                    assert!(from == to || !reachable_without(&reduced, from, to));
+                }
+            }
+        }
+    }
+}
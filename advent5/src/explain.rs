@@ -0,0 +1,167 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use crate::validate::CycleError;
+use crate::{correct_using_rules, parse_input};
+
+/// A rule `X|Y` an update breaks by printing `Y` before `X`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Violation {
+    pub before: u8,
+    pub after: u8,
+    /// Index in the update of the page that should come first, printed after the other
+    pub before_index: usize,
+    pub after_index: usize,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}|{}: {} at index {}, {} at index {}",
+            self.before, self.after, self.before, self.before_index, self.after, self.after_index
+        )
+    }
+}
+
+/// Every rule the update breaks, by the index of the page printed too early,
+/// then of the one printed too late. The first is the pair found by
+/// [`crate::get_changes_breaking_rules_by_index`].
+pub fn find_violations(changes: &[u8], rules: &HashMap<u8, BTreeSet<u8>>) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    for (i, &after) in changes.iter().enumerate() {
+        for (j, &before) in changes.iter().enumerate().skip(i + 1) {
+            if rules
+                .get(&before)
+                .is_some_and(|afters| afters.contains(&after))
+            {
+                violations.push(Violation {
+                    before,
+                    after,
+                    before_index: j,
+                    after_index: i,
+                });
+            }
+        }
+    }
+    violations
+}
+
+/// Why an update is in the wrong order, and how it should be
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// Number of the update, starting from 1
+    pub update: usize,
+    pub changes: Vec<u8>,
+    pub violations: Vec<Violation>,
+    pub corrected: Result<Vec<u8>, CycleError>,
+}
+
+/// A report for each update in the wrong order
+pub fn report(input: &str) -> Vec<Report> {
+    let (rules, changes) = parse_input(input);
+
+    changes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, update)| {
+            let violations = find_violations(update, &rules);
+            (!violations.is_empty()).then(|| Report {
+                update: i + 1,
+                changes: update.clone(),
+                violations,
+                corrected: correct_using_rules(update, &rules),
+            })
+        })
+        .collect()
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |pages: &[u8]| {
+            pages
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+        };
+
+        writeln!(f, "Update {}: {}", self.update, join(&self.changes))?;
+        for violation in &self.violations {
+            writeln!(f, "  {violation}")?;
+        }
+        match &self.corrected {
+            Ok(corrected) => write!(f, "  corrected: {}", join(corrected)),
+            Err(e) => write!(f, "  {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{get_changes_breaking_rules_by_index, get_rules};
+    use std::fs;
+
+    #[test]
+    fn reports_the_example() {
+        let input: String =
+            fs::read_to_string("test_input.txt").expect("Could not read from the file");
+        let reports: Vec<String> = report(&input).iter().map(|r| r.to_string()).collect();
+        assert_eq!(
+            vec![
+                "Update 4: 75,97,47,61,53\n  \
+                 97|75: 97 at index 1, 75 at index 0\n  \
+                 corrected: 97,75,47,61,53",
+                "Update 5: 61,13,29\n  \
+                 29|13: 29 at index 2, 13 at index 1\n  \
+                 corrected: 61,29,13",
+                "Update 6: 97,13,75,29,47\n  \
+                 75|13: 75 at index 2, 13 at index 1\n  \
+                 29|13: 29 at index 3, 13 at index 1\n  \
+                 47|13: 47 at index 4, 13 at index 1\n  \
+                 47|29: 47 at index 4, 29 at index 3\n  \
+                 corrected: 97,75,47,29,13",
+            ],
+            reports
+        );
+    }
+
+    #[test]
+    fn reports_updates_with_cycles() {
+        let reports = report("1|2\n2|1\n\n1,2\n");
+        assert_eq!(
+            "Update 1: 1,2\n  \
+             2|1: 2 at index 1, 1 at index 0\n  \
+             cannot order 1,2; cycle among 1, 2: 1 -> 2 -> 1",
+            reports[0].to_string()
+        );
+    }
+
+    #[test]
+    fn starts_with_the_first_conflict() {
+        let mut next = crate::tests::rng(50);
+
+        for _ in 0..500 {
+            let rules_string: Vec<String> = (0..next(40))
+                .map(|_| format!("{}|{}", next(15), next(15)))
+                .collect();
+            let rules = get_rules(rules_string.iter().map(|r| r.as_str()).collect());
+            let changes: Vec<u8> = (0..next(10)).map(|_| next(15) as u8).collect();
+
+            let violations = find_violations(&changes, &rules);
+            assert_eq!(
+                get_changes_breaking_rules_by_index(&changes, &rules),
+                violations.first().map(|v| (v.after_index, v.before_index))
+            );
+            for v in violations {
+                assert!(v.after_index < v.before_index);
+                assert_eq!(
+                    (v.before, v.after),
+                    (changes[v.before_index], changes[v.after_index])
+                );
+                assert!(rules[&v.before].contains(&v.after));
+            }
+        }
+    }
+}
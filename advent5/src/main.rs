@@ -1,7 +1,18 @@
+use std::env;
 use std::fs;
+use std::process;
 
 use std::collections::{BTreeSet, HashMap};
 
+mod dot;
+mod explain;
+mod order;
+mod validate;
+
+use dot::DotOptions;
+use order::topological_order;
+use validate::CycleError;
+
 fn get_rules(rules: Vec<&str>) -> HashMap<u8, BTreeSet<u8>> {
     let mut res: HashMap<u8, BTreeSet<u8>> = HashMap::new();
 
@@ -17,6 +28,31 @@ fn get_rules(rules: Vec<&str>) -> HashMap<u8, BTreeSet<u8>> {
     res
 }
 
+/// The page ordering rules and the updates of the puzzle input
+fn parse_input(input: &str) -> (HashMap<u8, BTreeSet<u8>>, Vec<Vec<u8>>) {
+    let rules_string: Vec<&str> = input
+        .lines()
+        .take_while(|line| !line.is_empty()) // Take lines until the first empty line
+        .collect();
+    let changes_string: Vec<&str> = input
+        .lines()
+        .skip_while(|line| !line.is_empty())
+        .skip(1)
+        .collect();
+
+    let rules = get_rules(rules_string);
+    let changes: Vec<Vec<u8>> = changes_string
+        .iter()
+        .map(|line| {
+            line.split(",")
+                .map(|el| el.parse().unwrap())
+                .collect::<Vec<u8>>()
+        })
+        .collect();
+
+    (rules, changes)
+}
+
 fn get_changes_breaking_rules_by_index(
     changes: &[u8],
     rules: &HashMap<u8, BTreeSet<u8>>,
@@ -103,11 +139,11 @@ fn get_changes_breaking_rules_by_index(
 /// In the above example, the first update (`75,47,61,53,29`) is in the right order:
 ///
 /// - `75` is correctly first because there are rules that put
-///    each other page after it: `75|47`, `75|61`, `75|53`, and `75|29`.
+///   each other page after it: `75|47`, `75|61`, `75|53`, and `75|29`.
 /// - `47` is correctly second because `75` must be before it
-///    (`75|47`) and every other page must be after it according to `47|61`, `47|53`, and `47|29`.
+///   (`75|47`) and every other page must be after it according to `47|61`, `47|53`, and `47|29`.
 /// - `61` is correctly in the middle because `75` and `47` are before it
-///    (`75|61` and `47|61`) and `53` and `29` are after it (`61|53` and `61|29`).
+///   (`75|61` and `47|61`) and `53` and `29` are after it (`61|53` and `61|29`).
 /// - `53` is correctly fourth because it is before page number `29` (`53|29`).
 /// - `29` is the only page left and so is correctly last.
 ///
@@ -146,25 +182,7 @@ fn get_changes_breaking_rules_by_index(
 /// Determine which updates are already in the correct order.
 /// What do you get if you add up the middle page number from those correctly-ordered updates?
 fn get_sum_middle_pages(input: &str) -> u32 {
-    let rules_string: Vec<&str> = input
-        .lines()
-        .take_while(|line| !line.is_empty()) // Take lines until the first empty line
-        .collect();
-    let changes_string: Vec<&str> = input
-        .lines()
-        .skip_while(|line| !line.is_empty())
-        .skip(1)
-        .collect();
-
-    let rules = get_rules(rules_string);
-    let changes: Vec<Vec<u8>> = changes_string
-        .iter()
-        .map(|line| {
-            line.split(",")
-                .map(|el| el.parse().unwrap())
-                .collect::<Vec<u8>>()
-        })
-        .collect();
+    let (rules, changes) = parse_input(input);
 
     changes
         .iter()
@@ -181,19 +199,13 @@ fn get_sum_middle_pages(input: &str) -> u32 {
         .sum::<u32>()
 }
 
-fn correct_using_rules(changes: &[u8], rules: &HashMap<u8, BTreeSet<u8>>) -> Vec<u8> {
-    let mut res = Vec::from(changes);
-
-    loop {
-        let c: Option<(usize, usize)> = get_changes_breaking_rules_by_index(&res, rules);
-        if c.is_none() {
-            break;
-        }
-        let c = c.unwrap();
-        res.swap(c.0, c.1);
-    }
-
-    res
+/// The pages of an update put in an order following the rules, an error with
+/// the cycles among them when there is none
+fn correct_using_rules(
+    changes: &[u8],
+    rules: &HashMap<u8, BTreeSet<u8>>,
+) -> Result<Vec<u8>, CycleError> {
+    topological_order(changes, rules)
 }
 
 /// While the Elves get to work printing the correctly-ordered updates,
@@ -212,26 +224,8 @@ fn correct_using_rules(changes: &[u8], rules: &HashMap<u8, BTreeSet<u8>>) -> Vec
 ///
 /// Find the updates which are not in the correct order. What do you get if you
 /// add up the middle page numbers after correctly ordering just those updates?
-fn get_sum_middle_pages_incorrect_but_ordered(input: &str) -> u32 {
-    let rules_string: Vec<&str> = input
-        .lines()
-        .take_while(|line| !line.is_empty()) // Take lines until the first empty line
-        .collect();
-    let changes_string: Vec<&str> = input
-        .lines()
-        .skip_while(|line| !line.is_empty())
-        .skip(1)
-        .collect();
-
-    let rules = get_rules(rules_string);
-    let changes: Vec<Vec<u8>> = changes_string
-        .iter()
-        .map(|line| {
-            line.split(",")
-                .map(|el| el.parse().unwrap())
-                .collect::<Vec<u8>>()
-        })
-        .collect();
+fn get_sum_middle_pages_incorrect_but_ordered(input: &str) -> Result<u32, CycleError> {
+    let (rules, changes) = parse_input(input);
 
     let is_correct = changes
         .iter()
@@ -243,22 +237,123 @@ fn get_sum_middle_pages_incorrect_but_ordered(input: &str) -> u32 {
         .enumerate()
         .map(|(i, _)| {
             if !is_correct.get(i).unwrap() {
-                correct_using_rules(&changes[i], &rules)[changes[i].len() / 2] as u32
+                Ok(correct_using_rules(&changes[i], &rules)?[changes[i].len() / 2] as u32)
             } else {
-                0
+                Ok(0)
             }
         })
         .sum()
 }
 
+/// Prints the update that could not be ordered and stops
+fn exit_on_cycle<T>(result: Result<T, CycleError>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("Could not order the updates: {e}");
+        process::exit(1);
+    })
+}
+
+/// Prints the cycles of the whole set of rules, then the ones of each update
+/// that can't be ordered, with its number counting from 1
+fn print_cycles(rules: &HashMap<u8, BTreeSet<u8>>, changes: &[Vec<u8>]) {
+    let cycles = validate::rule_cycles(rules);
+    println!("Rules: {} cycles", cycles.len());
+    for cycle in cycles {
+        println!("  {cycle}");
+    }
+
+    for (i, update) in changes.iter().enumerate() {
+        let cycles = validate::update_cycles(update, rules);
+        if cycles.is_empty() {
+            continue;
+        }
+        let pages: Vec<String> = update.iter().map(|p| p.to_string()).collect();
+        println!(
+            "Update {} ({}): {} cycles",
+            i + 1,
+            pages.join(","),
+            cycles.len()
+        );
+        for cycle in cycles {
+            println!("  {cycle}");
+        }
+    }
+}
+
+/// Usage: `advent5 [--input FILE] [--validate] [--dot] [--update N] [--all-pages] [--reduce]
+/// [--report]`
+///
+/// With `--input`, the rules and updates are read from `FILE` instead of `input.txt`.
+/// With `--validate`, the cycles of the rules are listed instead, first the ones of
+/// the whole set of rules, which updates only follow in part, then the ones among
+/// the pages of each update, which make it impossible to order.
+/// With `--dot`, the rules are printed as a Graphviz graph instead. With `--update`,
+/// only the pages of the `N`th update are drawn, all of them with `--all-pages`,
+/// and the rules it breaks are in red. With `--reduce`, the rules following from
+/// the others are left out. With `--report`, each update in the wrong order is
+/// printed instead, with every rule it breaks and the order it should be in.
+/// `--validate`, `--dot` and `--report` don't go together.
 fn main() {
-    let input: String =
-        fs::read_to_string("input.txt").expect("Could not read from the input file");
+    let mut path = String::from("input.txt");
+    let mut validate = false;
+    let mut dot = false;
+    let mut report = false;
+    let mut update: Option<usize> = None;
+    let mut options = DotOptions::default();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| panic!("Missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--input" => path = value(),
+            "--validate" => validate = true,
+            "--dot" => dot = true,
+            "--update" => update = Some(value().parse().unwrap()),
+            "--all-pages" => options.all_pages = true,
+            "--reduce" => options.reduce = true,
+            "--report" => report = true,
+            _ => panic!("Unknown argument: {arg}"),
+        }
+    }
+    if validate as u8 + dot as u8 + report as u8 > 1 {
+        panic!("Only one of --validate, --dot and --report at a time");
+    }
+    if !dot && (update.is_some() || options.all_pages || options.reduce) {
+        panic!("--update, --all-pages and --reduce only go with --dot");
+    }
+
+    let input: String = fs::read_to_string(path).expect("Could not read from the input file");
+
+    if validate {
+        let (rules, changes) = parse_input(&input);
+        print_cycles(&rules, &changes);
+        return;
+    }
+    if report {
+        for r in explain::report(&input) {
+            println!("{r}");
+        }
+        return;
+    }
+    if dot {
+        let (rules, changes) = parse_input(&input);
+        options.update = update.map(|n| {
+            changes
+                .get(n.wrapping_sub(1))
+                .unwrap_or_else(|| panic!("No update {n}"))
+                .clone()
+        });
+        print!("{}", dot::to_dot(&rules, &options));
+        return;
+    }
 
     println!("Sum of correct: {}", get_sum_middle_pages(&input));
     println!(
         "Sum of incorrect: {}",
-        get_sum_middle_pages_incorrect_but_ordered(&input)
+        exit_on_cycle(get_sum_middle_pages_incorrect_but_ordered(&input))
     );
 }
 
@@ -266,6 +361,16 @@ fn main() {
 mod tests {
     use super::*;
 
+    /// A xorshift generator of numbers below `n`, for the randomized tests
+    pub fn rng(mut seed: u32) -> impl FnMut(u32) -> u32 {
+        move |n| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed % n
+        }
+    }
+
     #[test]
     fn given_test_part_one() {
         let input: String =
@@ -279,6 +384,16 @@ mod tests {
         let input: String =
             fs::read_to_string("test_input.txt").expect("Could not read from the file");
         let e = 123;
-        assert_eq!(e, get_sum_middle_pages_incorrect_but_ordered(&input))
+        assert_eq!(Ok(e), get_sum_middle_pages_incorrect_but_ordered(&input))
+    }
+
+    #[test]
+    fn fails_on_cycles_instead_of_hanging() {
+        let input = "1|2\n2|3\n3|1\n\n1,2\n2,1\n3,2,1\n";
+        let err = get_sum_middle_pages_incorrect_but_ordered(input).unwrap_err();
+        assert_eq!(vec![3, 2, 1], err.changes);
+        assert_eq!(vec![1, 2, 3, 1], err.cycles[0].path);
+        // The first part only checks the rules, it has nothing to order
+        assert_eq!(2, get_sum_middle_pages(input));
     }
 }
@@ -0,0 +1,133 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashMap};
+
+use crate::validate::{update_cycles, CycleError};
+
+/// The pages of an update in an order following every rule between them,
+/// found by a topological sort of the rules restricted to these pages
+/// (Kahn's algorithm). An error with the cycles when those rules contain any.
+///
+/// Of the pages free to come next, the one earliest in the update always does,
+/// so the pages the rules don't order keep their relative order and an update
+/// that is already correct is left as it is.
+pub fn topological_order(
+    changes: &[u8],
+    rules: &HashMap<u8, BTreeSet<u8>>,
+) -> Result<Vec<u8>, CycleError> {
+    // Positions rather than pages are the nodes, in case a page is printed twice
+    let mut positions: HashMap<u8, Vec<usize>> = HashMap::new();
+    for (i, &page) in changes.iter().enumerate() {
+        positions.entry(page).or_default().push(i);
+    }
+
+    let mut successors: Vec<Vec<usize>> = vec![Vec::new(); changes.len()];
+    let mut predecessors: Vec<usize> = vec![0; changes.len()];
+    for (i, page) in changes.iter().enumerate() {
+        for after in rules.get(page).into_iter().flatten() {
+            for &j in positions.get(after).into_iter().flatten() {
+                successors[i].push(j);
+                predecessors[j] += 1;
+            }
+        }
+    }
+
+    let mut ready: BinaryHeap<Reverse<usize>> = (0..changes.len())
+        .filter(|&i| predecessors[i] == 0)
+        .map(Reverse)
+        .collect();
+    let mut res = Vec::with_capacity(changes.len());
+    while let Some(Reverse(i)) = ready.pop() {
+        res.push(changes[i]);
+        for &j in &successors[i] {
+            predecessors[j] -= 1;
+            if predecessors[j] == 0 {
+                ready.push(Reverse(j));
+            }
+        }
+    }
+
+    if res.len() < changes.len() {
+        // The pages left over all wait on one another
+        return Err(CycleError {
+            changes: changes.to_vec(),
+            cycles: update_cycles(changes, rules),
+        });
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{get_changes_breaking_rules_by_index, get_rules};
+
+    #[test]
+    fn orders_the_example() {
+        let rules = get_rules(vec!["97|75", "75|47", "29|13", "47|29", "75|13"]);
+        assert_eq!(
+            Ok(vec![97, 75, 47, 29, 13]),
+            topological_order(&[13, 29, 47, 75, 97], &rules)
+        );
+        // The rules say nothing about 5 and 8, so they come as early as they can
+        assert_eq!(
+            Ok(vec![5, 8, 97, 75]),
+            topological_order(&[5, 75, 8, 97], &rules)
+        );
+        assert_eq!(Ok(vec![]), topological_order(&[], &rules));
+    }
+
+    #[test]
+    fn detects_cycles() {
+        let rules = get_rules(vec!["1|2", "2|3", "3|1", "4|1"]);
+        let err = topological_order(&[3, 2, 1], &rules).unwrap_err();
+        assert_eq!(
+            "cannot order 3,2,1; cycle among 1, 2, 3: 1 -> 2 -> 3 -> 1",
+            err.to_string()
+        );
+        let err = topological_order(&[4, 1, 2, 3], &rules).unwrap_err();
+        assert_eq!(vec![1, 2, 3], err.cycles[0].pages);
+        // Without one of its pages, the cycle doesn't apply
+        assert_eq!(Ok(vec![4, 1, 2]), topological_order(&[2, 1, 4], &rules));
+    }
+
+    #[test]
+    fn follows_random_rules() {
+        let mut next = crate::tests::rng(47);
+
+        for _ in 0..500 {
+            // Rules going from a smaller page to a larger one can't make a cycle
+            let mut rules_string = Vec::new();
+            for _ in 0..next(60) {
+                let (a, b) = (next(20), next(20));
+                if a < b {
+                    rules_string.push(format!("{a}|{b}"));
+                }
+            }
+            let rules = get_rules(rules_string.iter().map(|r| r.as_str()).collect());
+            let changes: Vec<u8> = (0..next(12)).map(|_| next(20) as u8).collect();
+
+            let res = topological_order(&changes, &rules).unwrap();
+            assert_eq!(None, get_changes_breaking_rules_by_index(&res, &rules));
+            let (mut sorted, mut expected) = (res.clone(), changes.clone());
+            sorted.sort();
+            expected.sort();
+            assert_eq!(expected, sorted);
+        }
+    }
+
+    #[test]
+    fn orders_long_updates() {
+        // Every page before every larger one, the update in the reverse order
+        let mut rules_string = Vec::new();
+        for a in 0..=255 {
+            for b in a + 1..=255 {
+                rules_string.push(format!("{a}|{b}"));
+            }
+        }
+        let rules = get_rules(rules_string.iter().map(|r| r.as_str()).collect());
+        let changes: Vec<u8> = (0..=255).rev().collect();
+
+        let res = topological_order(&changes, &rules).unwrap();
+        assert_eq!((0..=255).collect::<Vec<u8>>(), res);
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+
+/// Pages of the rules that all have to come before one another, impossible to print
+/// in any order: a strongly connected component of the rule graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cycle {
+    /// Every page of the component, in increasing order
+    pub pages: Vec<u8>,
+    /// One of the shortest cycles through the smallest page, which it starts and ends with
+    pub path: Vec<u8>,
+}
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |pages: &[u8], sep| {
+            pages
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<String>>()
+                .join(sep)
+        };
+        write!(
+            f,
+            "cycle among {}: {}",
+            join(&self.pages, ", "),
+            join(&self.path, " -> ")
+        )
+    }
+}
+
+/// The rules between the pages of an update make it impossible to order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleError {
+    pub changes: Vec<u8>,
+    pub cycles: Vec<Cycle>,
+}
+
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let changes: Vec<String> = self.changes.iter().map(|p| p.to_string()).collect();
+        write!(f, "cannot order {}", changes.join(","))?;
+        for cycle in &self.cycles {
+            write!(f, "; {cycle}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CycleError {}
+
+/// Tarjan's algorithm over the rules between `pages`
+struct Components<'a> {
+    pages: &'a BTreeSet<u8>,
+    rules: &'a HashMap<u8, BTreeSet<u8>>,
+    index: HashMap<u8, usize>,
+    low: HashMap<u8, usize>,
+    stack: Vec<u8>,
+    on_stack: BTreeSet<u8>,
+    components: Vec<Vec<u8>>,
+}
+
+impl Components<'_> {
+    fn successors(&self, page: u8) -> impl Iterator<Item = u8> + '_ {
+        self.rules
+            .get(&page)
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|p| self.pages.contains(p))
+    }
+
+    fn visit(&mut self, page: u8) {
+        let index = self.index.len();
+        self.index.insert(page, index);
+        self.low.insert(page, index);
+        self.stack.push(page);
+        self.on_stack.insert(page);
+
+        for next in self.successors(page).collect::<Vec<u8>>() {
+            if !self.index.contains_key(&next) {
+                self.visit(next);
+                self.low.insert(page, self.low[&page].min(self.low[&next]));
+            } else if self.on_stack.contains(&next) {
+                self.low
+                    .insert(page, self.low[&page].min(self.index[&next]));
+            }
+        }
+
+        if self.low[&page] == index {
+            let mut component = Vec::new();
+            while let Some(p) = self.stack.pop() {
+                self.on_stack.remove(&p);
+                component.push(p);
+                if p == page {
+                    break;
+                }
+            }
+            component.sort();
+            self.components.push(component);
+        }
+    }
+
+    /// A shortest path from the first page of the component back to it
+    fn shortest_cycle(&self, component: &[u8]) -> Vec<u8> {
+        let start = component[0];
+        let mut previous: HashMap<u8, u8> = HashMap::new();
+        let mut queue = VecDeque::from([start]);
+        while let Some(page) = queue.pop_front() {
+            for next in self.successors(page) {
+                if next == start {
+                    let mut path = vec![start, page];
+                    while let Some(&p) = previous.get(path.last().unwrap()) {
+                        path.push(p);
+                    }
+                    path.reverse();
+                    return path;
+                }
+                if component.binary_search(&next).is_ok() && !previous.contains_key(&next) {
+                    previous.insert(next, page);
+                    queue.push_back(next);
+                }
+            }
+        }
+        unreachable!("Every page of a component is on a cycle")
+    }
+}
+
+/// Every set of pages among `pages` that the rules between them put in a cycle,
+/// ordered by their smallest page. A page with a rule putting it before itself is one.
+pub fn cycles_among(pages: &BTreeSet<u8>, rules: &HashMap<u8, BTreeSet<u8>>) -> Vec<Cycle> {
+    let mut components = Components {
+        pages,
+        rules,
+        index: HashMap::new(),
+        low: HashMap::new(),
+        stack: Vec::new(),
+        on_stack: BTreeSet::new(),
+        components: Vec::new(),
+    };
+    for &page in pages {
+        if !components.index.contains_key(&page) {
+            components.visit(page);
+        }
+    }
+
+    let mut cycles: Vec<Cycle> = components
+        .components
+        .iter()
+        .filter(|c| c.len() > 1 || components.successors(c[0]).any(|p| p == c[0]))
+        .map(|c| Cycle {
+            pages: c.clone(),
+            path: components.shortest_cycle(c),
+        })
+        .collect();
+    cycles.sort_by_key(|c| c.pages[0]);
+    cycles
+}
+
+/// The cycles of the whole set of rules, whatever the updates
+pub fn rule_cycles(rules: &HashMap<u8, BTreeSet<u8>>) -> Vec<Cycle> {
+    let pages: BTreeSet<u8> = rules
+        .iter()
+        .flat_map(|(before, afters)| afters.iter().chain([before]))
+        .copied()
+        .collect();
+    cycles_among(&pages, rules)
+}
+
+/// The cycles of the rules between the pages of an update, the only ones it has to follow
+pub fn update_cycles(changes: &[u8], rules: &HashMap<u8, BTreeSet<u8>>) -> Vec<Cycle> {
+    cycles_among(&changes.iter().copied().collect(), rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_rules;
+    use std::fs;
+
+    #[test]
+    fn the_example_has_no_cycle() {
+        let input: String =
+            fs::read_to_string("test_input.txt").expect("Could not read from the file");
+        let rules = get_rules(input.lines().take_while(|l| !l.is_empty()).collect());
+        assert_eq!(Vec::<Cycle>::new(), rule_cycles(&rules));
+    }
+
+    #[test]
+    fn finds_every_cycle() {
+        let rules = get_rules(vec![
+            "1|2", "2|3", "3|1", "3|4", "4|5", "5|6", "6|4", "2|7", "7|7", "8|1",
+        ]);
+        let cycles = rule_cycles(&rules);
+        assert_eq!(
+            vec![
+                "cycle among 1, 2, 3: 1 -> 2 -> 3 -> 1",
+                "cycle among 4, 5, 6: 4 -> 5 -> 6 -> 4",
+                "cycle among 7: 7 -> 7",
+            ],
+            cycles
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<String>>()
+        );
+
+        // Only the rules between the pages of the update count
+        assert_eq!(Vec::<Cycle>::new(), update_cycles(&[1, 2, 4, 5, 8], &rules));
+        assert_eq!(
+            vec!["cycle among 4, 5, 6: 4 -> 5 -> 6 -> 4"],
+            update_cycles(&[8, 6, 5, 4, 1], &rules)
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<String>>()
+        );
+    }
+
+    #[test]
+    fn finds_the_shortest_cycle() {
+        let rules = get_rules(vec!["1|2", "2|3", "3|4", "4|1", "2|1", "3|2"]);
+        let cycles = rule_cycles(&rules);
+        assert_eq!(1, cycles.len());
+        assert_eq!(
+            (vec![1, 2, 3, 4], vec![1, 2, 1]),
+            (cycles[0].pages.clone(), cycles[0].path.clone())
+        );
+    }
+}